@@ -10,3 +10,5 @@ openssl req -newkey rsa:2048 -days 3600 -nodes -subj "/C=GB/ST=London/L=London/C
 openssl rsa -in certs/client-key.pem -out certs/client-key.pem
 openssl x509 -req -in certs/client-req.pem -days 3600 -CA certs/root-ca.pem -CAkey certs/root-ca-key.pem -set_serial 01 -out certs/client-cert.pem
 openssl verify -CAfile certs/root-ca.pem certs/client-cert.pem
+# sqlx with native-tls only loads client keys in PKCS#8 format
+openssl pkcs8 -topk8 -nocrypt -in certs/client-key.pem -out certs/client-key.pk8.pem
@@ -1,9 +1,16 @@
-use database_schema::DatabaseSchemaBuilder;
+use database_schema::{DatabaseSchemaBuilder, TlsMode, TlsOptions};
 
 #[tokio::main]
 async fn main() -> Result<(), database_schema::Error> {
     DatabaseSchemaBuilder::new()
-        .connection_url("mysql://root:@127.0.0.1:3306/example?ssl_mode=verify_ca&ssl_key=certs/client-key.pem&ssl_ca=certs/root-ca.pem&ssl_cert=certs/client-cert.pem")
+        .connection_url("mysql://root:@127.0.0.1:3306/example")
+        .tls(
+            TlsOptions::new()
+                .mode(TlsMode::VerifyCa)
+                .root_cert("certs/root-ca.pem")
+                .client_cert("certs/client-cert.pem")
+                .client_key("certs/client-key.pk8.pem"),
+        )
         .migrations_dir("./migrations")?
         .destination_path("./structure.sql")
         .build()
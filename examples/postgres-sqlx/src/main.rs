@@ -1,17 +1,18 @@
-use database_schema::DatabaseSchemaBuilder;
+use database_schema::{DatabaseSchemaBuilder, TlsMode, TlsOptions};
 
 #[tokio::main]
 async fn main() -> Result<(), database_schema::Error> {
-    // ssl seems to not be working due to tls-native and tls-rustls shenanigans I haven't
-    // figured out yet.
-    //
-    // In principle, it should work without code changes - only upstream changes.
-    //
-    // See more at https://github.com/launchbadge/sqlx/issues/1162
-
+    // The same TLS settings are used to run the migrations and by `pg_dump`. With sqlx and
+    // `native-tls`, the client key has to be in PKCS#8 format (see `client-key.pk8.pem`).
     DatabaseSchemaBuilder::new()
         .connection_url("postgresql://postgres@127.0.0.1:5432/postgres")
-        //.connection_url("postgresql://postgres@127.0.0.1:5432/postgres?sslmode=verify-ca&sslcert=certs/client-cert.pem&sslkey=certs/client-key.pem&sslrootcert=certs/root-ca.pem")
+        .tls(
+            TlsOptions::new()
+                .mode(TlsMode::VerifyCa)
+                .root_cert("certs/root-ca.pem")
+                .client_cert("certs/client-cert.pem")
+                .client_key("certs/client-key.pk8.pem"),
+        )
         .migrations_dir("./migrations")?
         .destination_path("./structure.sql")
         .build()
@@ -0,0 +1,5 @@
+CREATE TABLE cli_users (
+  id VARCHAR(32) PRIMARY KEY NOT NULL,
+  email TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
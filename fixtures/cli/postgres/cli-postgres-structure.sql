@@ -0,0 +1,56 @@
+SET statement_timeout = 0;
+SET lock_timeout = 0;
+SET idle_in_transaction_session_timeout = 0;
+SET client_encoding = 'UTF8';
+SET standard_conforming_strings = on;
+SELECT pg_catalog.set_config('search_path', '', false);
+SET check_function_bodies = false;
+SET xmloption = content;
+SET client_min_messages = warning;
+SET row_security = off;
+
+SET default_tablespace = '';
+
+SET default_table_access_method = heap;
+
+--
+-- Name: _database_schema_migrations; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public._database_schema_migrations (
+    version character varying(255) NOT NULL,
+    name character varying(255) NOT NULL,
+    applied_at timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+
+--
+-- Name: cli_users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.cli_users (
+    id character varying(32) NOT NULL,
+    email text NOT NULL,
+    created_at timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+
+--
+-- Name: _database_schema_migrations _database_schema_migrations_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public._database_schema_migrations
+    ADD CONSTRAINT _database_schema_migrations_pkey PRIMARY KEY (version);
+
+
+--
+-- Name: cli_users cli_users_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.cli_users
+    ADD CONSTRAINT cli_users_pkey PRIMARY KEY (id);
+
+
+--
+-- PostgreSQL database dump complete
+--
@@ -0,0 +1,5 @@
+CREATE TABLE users (
+  id TEXT PRIMARY KEY NOT NULL,
+  email TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT(datetime('now', 'utc'))
+);
@@ -0,0 +1,2 @@
+-- Forgets to drop the index on users
+DROP TABLE posts;
@@ -0,0 +1,5 @@
+CREATE TABLE posts (
+  id TEXT PRIMARY KEY NOT NULL,
+  user_id TEXT NOT NULL REFERENCES users (id)
+);
+CREATE INDEX users_email ON users (email);
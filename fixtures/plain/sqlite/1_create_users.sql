@@ -0,0 +1,5 @@
+-- Hand-written migrations, applied in version order
+CREATE TABLE users (
+  id TEXT PRIMARY KEY NOT NULL,
+  email TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+CREATE TABLE posts (
+  id TEXT PRIMARY KEY NOT NULL,
+  user_id TEXT NOT NULL REFERENCES users (id)
+);
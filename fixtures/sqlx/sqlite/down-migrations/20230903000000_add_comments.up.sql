@@ -0,0 +1,4 @@
+CREATE TABLE comments (
+  id TEXT PRIMARY KEY NOT NULL,
+  post_id TEXT NOT NULL REFERENCES posts (id)
+);
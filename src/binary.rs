@@ -0,0 +1,277 @@
+//! Discovery of the external dump tools (`pg_dump` and `mysqldump`).
+//!
+//! `pg_dump` has to be at least as recent as the server it dumps, otherwise it refuses
+//! to run. Since it's common to have several clients installed side by side, we look for
+//! all of them and pick the oldest one that can dump the server. `mysqldump` has no such
+//! rule (and MariaDB numbers its versions differently), so the first one found is used,
+//! with `--column-statistics=0` when a MySQL 8 client dumps an older or MariaDB server.
+
+use crate::error::Error;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// An external program used to dump the database structure.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DumpTool {
+    /// Program name, as found in `PATH`
+    pub(crate) name: &'static str,
+    /// Environment variable that can point to the program
+    pub(crate) env_var: &'static str,
+    /// Versioned install directories, as `(parent, prefix)`: every directory in
+    /// `parent` whose name starts with `prefix` is searched for a `bin/<name>`.
+    pub(crate) install_dirs: &'static [(&'static str, &'static str)],
+    /// Which client versions can dump a given server
+    pub(crate) compatibility: Compatibility,
+}
+
+/// How the version of a dump tool has to relate to the version of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compatibility {
+    /// The major version of the client has to be at least the one of the server.
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    AtLeastServerMajor,
+    /// Any version of the client works.
+    #[cfg_attr(not(feature = "mysql"), allow(dead_code))]
+    Any,
+}
+
+#[cfg(feature = "postgres")]
+pub(crate) const PG_DUMP: DumpTool = DumpTool {
+    name: "pg_dump",
+    env_var: "PG_DUMP",
+    install_dirs: &[
+        ("/usr/lib/postgresql", ""),
+        ("/usr", "pgsql-"),
+        ("/opt/homebrew/opt", "postgresql@"),
+        ("/opt/homebrew/opt", "libpq"),
+        ("/usr/local/opt", "postgresql@"),
+        ("/usr/local/opt", "libpq"),
+        ("/Applications/Postgres.app/Contents/Versions", ""),
+    ],
+    compatibility: Compatibility::AtLeastServerMajor,
+};
+
+#[cfg(feature = "mysql")]
+pub(crate) const MYSQLDUMP: DumpTool = DumpTool {
+    name: "mysqldump",
+    env_var: "MYSQLDUMP",
+    install_dirs: &[
+        ("/opt/homebrew/opt", "mysql"),
+        ("/usr/local/opt", "mysql"),
+        ("/usr/local", "mysql"),
+        ("/opt", "mysql"),
+    ],
+    compatibility: Compatibility::Any,
+};
+
+impl DumpTool {
+    /// Find the binary to use to dump a server running `server_version`.
+    ///
+    /// A `configured` path (from the builder) wins over the environment variable, which
+    /// wins over searching `PATH` and the versioned install directories. An explicitly
+    /// chosen binary is still checked against the server version, if the tool requires it.
+    pub(crate) fn find(
+        &self,
+        configured: Option<&Path>,
+        server_version: &str,
+    ) -> Result<PathBuf, Error> {
+        let server_major = major(server_version);
+        let explicit = configured
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(self.env_var).map(PathBuf::from));
+        let candidates = match explicit {
+            Some(path) => vec![path],
+            None => self.candidates(),
+        };
+
+        let versions = candidates
+            .into_iter()
+            .filter_map(|path| {
+                let version = self.version(&path)?;
+                Some((path, version))
+            })
+            .collect::<Vec<(PathBuf, String)>>();
+        tracing::debug!(
+            tool = self.name,
+            ?versions,
+            server_version,
+            "Found dump tools"
+        );
+
+        select(versions, server_major, self.compatibility).map_err(|client| match client {
+            Some(client) => Error::DumpToolVersionMismatch {
+                tool: self.name,
+                client,
+                server: server_version.to_owned(),
+            },
+            None => Error::DumpToolNotFound(self.name),
+        })
+    }
+
+    fn candidates(&self) -> Vec<PathBuf> {
+        let mut candidates = std::env::var_os("PATH")
+            .map(|path| {
+                std::env::split_paths(&path)
+                    .map(|dir| dir.join(self.name))
+                    .collect::<Vec<PathBuf>>()
+            })
+            .unwrap_or_default();
+        for (parent, prefix) in self.install_dirs {
+            let Ok(entries) = std::fs::read_dir(parent) else {
+                continue;
+            };
+            let mut dirs = entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+                .map(|entry| entry.path().join("bin").join(self.name))
+                .collect::<Vec<PathBuf>>();
+            dirs.sort();
+            candidates.extend(dirs);
+        }
+        let mut seen = HashSet::new();
+        candidates.retain(|path| path.is_file() && seen.insert(path.clone()));
+        candidates
+    }
+
+    /// Run `<binary> --version` and extract the version number from its output.
+    fn version(&self, path: &Path) -> Option<String> {
+        let output = std::process::Command::new(path)
+            .arg("--version")
+            .output()
+            .ok()?;
+        parse_version(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Pick the oldest binary whose major version is at least `server_major`, or the first
+/// one found if any version is [compatible](Compatibility::Any).
+///
+/// On failure, returns the most recent version found, if any, to report it.
+fn select(
+    mut versions: Vec<(PathBuf, String)>,
+    server_major: u32,
+    compatibility: Compatibility,
+) -> Result<PathBuf, Option<String>> {
+    if compatibility == Compatibility::Any {
+        return versions
+            .into_iter()
+            .next()
+            .map(|(path, _)| path)
+            .ok_or(None);
+    }
+    versions.sort_by_key(|(_, version)| major(version));
+    let newest = versions.last().map(|(_, version)| version.clone());
+    versions
+        .into_iter()
+        .find(|(_, version)| major(version) >= server_major)
+        .map(|(path, _)| path)
+        .ok_or(newest)
+}
+
+/// Extract the version from the output of `--version`:
+///
+/// * `pg_dump (PostgreSQL) 15.4 (Debian 15.4-1)` -> `15.4`
+/// * `mysqldump  Ver 8.0.35 for Linux on x86_64 (MySQL Community Server - GPL)` -> `8.0.35`
+/// * `mysqldump  Ver 10.19 Distrib 10.11.6-MariaDB, for debian-linux-gnu (x86_64)` -> `10.11.6`
+pub(crate) fn parse_version(output: &str) -> Option<String> {
+    let after = |marker: &str| {
+        output
+            .split_once(marker)
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+    };
+    let version = after("Distrib ")
+        .or_else(|| after("Ver "))
+        .or_else(|| after(") "))?;
+    let version = version
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.');
+    (!version.is_empty()).then(|| version.to_owned())
+}
+
+/// Major version of a `major.minor.patch` version string.
+pub(crate) fn major(version: &str) -> u32 {
+    version
+        .split('.')
+        .next()
+        .and_then(|major| major.parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compatibility;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            super::parse_version("pg_dump (PostgreSQL) 15.4 (Debian 15.4-1.pgdg120+1)\n"),
+            Some(String::from("15.4"))
+        );
+        assert_eq!(
+            super::parse_version("pg_dump (PostgreSQL) 17beta1\n"),
+            Some(String::from("17"))
+        );
+        assert_eq!(
+            super::parse_version(
+                "mysqldump  Ver 8.0.35 for Linux on x86_64 (MySQL Community Server - GPL)\n"
+            ),
+            Some(String::from("8.0.35"))
+        );
+        assert_eq!(
+            super::parse_version(
+                "mysqldump  Ver 10.19 Distrib 10.11.6-MariaDB, for debian-linux-gnu (x86_64)\n"
+            ),
+            Some(String::from("10.11.6"))
+        );
+        assert_eq!(super::parse_version("command not found"), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let versions = vec![
+            (
+                PathBuf::from("/usr/lib/postgresql/16/bin/pg_dump"),
+                String::from("16.1"),
+            ),
+            (PathBuf::from("/usr/bin/pg_dump"), String::from("13.9")),
+            (
+                PathBuf::from("/usr/lib/postgresql/14/bin/pg_dump"),
+                String::from("14.2"),
+            ),
+        ];
+        let rule = Compatibility::AtLeastServerMajor;
+        assert_eq!(
+            super::select(versions.clone(), 14, rule),
+            Ok(PathBuf::from("/usr/lib/postgresql/14/bin/pg_dump"))
+        );
+        assert_eq!(
+            super::select(versions.clone(), 12, rule),
+            Ok(PathBuf::from("/usr/bin/pg_dump"))
+        );
+        assert_eq!(
+            super::select(versions, 17, rule),
+            Err(Some(String::from("16.1")))
+        );
+        assert_eq!(super::select(Vec::new(), 17, rule), Err(None));
+    }
+
+    #[test]
+    fn test_select_any() {
+        // A MariaDB client dumping a MySQL 8 server.
+        let versions = vec![
+            (PathBuf::from("/usr/bin/mysqldump"), String::from("10.11.6")),
+            (
+                PathBuf::from("/usr/local/mysql-5.7/bin/mysqldump"),
+                String::from("5.7.44"),
+            ),
+        ];
+        assert_eq!(
+            super::select(versions, 8, Compatibility::Any),
+            Ok(PathBuf::from("/usr/bin/mysqldump"))
+        );
+        assert_eq!(super::select(Vec::new(), 8, Compatibility::Any), Err(None));
+    }
+}
@@ -0,0 +1,472 @@
+//! Project configuration with several named dump targets.
+//!
+//! Projects with more than one database can declare them all in a `database-schema.toml`
+//! file at the root of the project:
+//!
+//! ```toml
+//! [targets.main]
+//! url_env = "DATABASE_URL"
+//! backend = "postgres"
+//! migrations_dir = "migrations"
+//! destination = "structure.sql"
+//! history_dir = "schema-history"
+//! data_tables = ["roles", "permissions"]
+//! exclude_tables = ["audit.events"]
+//! header = { generated_at = false }
+//! postgres_extensions = { keep_create_extension = true }
+//!
+//! [targets.cache]
+//! url = "sqlite://cache.sqlite3?mode=rwc"
+//! migrations_dir = "cache/migrations"
+//! destination = "cache/structure.sql"
+//!
+//! [targets.v1]
+//! migrations_dir = "migrations"
+//! destination = "fixtures/structure-v1.sql"
+//! target_version = "20240101000000"
+//! ```
+//!
+//! The same tables can live in `Cargo.toml` under `[package.metadata.database-schema]`
+//! instead (e.g. `[package.metadata.database-schema.targets.main]`).
+//!
+//! Relative paths are resolved from the directory containing the configuration file.
+//!
+//! Each target picks the tables in the dump with `data_tables` and `exclude_tables`, and
+//! what surrounds them with `header` and `postgres_extensions`. The dump is always
+//! written as SQL, there is no other output format.
+//!
+//! ```rust,ignore
+//! let config = ProjectConfig::discover()?;
+//! config.dump("main").await?;
+//! config.dump_all().await?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{error::Error, Backend, DatabaseSchemaBuilder, HeaderOptions};
+
+/// Name of the configuration file looked up by [`ProjectConfig::discover`].
+pub const CONFIG_FILE_NAME: &str = "database-schema.toml";
+
+/// A set of named dump targets, usually loaded from `database-schema.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    targets: BTreeMap<String, TargetConfig>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+/// Configuration of a single dump target.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetConfig {
+    /// Connection URL. Takes precedence over `url_env`.
+    url: Option<String>,
+    /// Name of the environment variable holding the connection URL.
+    url_env: Option<String>,
+    /// Expected backend, checked against the connection URL.
+    backend: Option<String>,
+    migrations_dir: Option<PathBuf>,
+    destination: Option<PathBuf>,
+    history_dir: Option<PathBuf>,
+    /// Version of the last migration to apply.
+    target_version: Option<String>,
+    /// Tables whose rows are appended to the dump.
+    data_tables: Option<Vec<String>>,
+    /// Tables left out of the dump.
+    exclude_tables: Option<Vec<String>>,
+    header: Option<HeaderConfig>,
+    postgres_extensions: Option<ExtensionsConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeaderConfig {
+    crate_version: Option<bool>,
+    backend: Option<bool>,
+    latest_migration: Option<bool>,
+    migrations_checksum: Option<bool>,
+    generated_at: Option<bool>,
+}
+
+impl From<HeaderConfig> for HeaderOptions {
+    fn from(config: HeaderConfig) -> Self {
+        let defaults = HeaderOptions::new();
+        HeaderOptions::new()
+            .crate_version(config.crate_version.unwrap_or(defaults.crate_version))
+            .backend(config.backend.unwrap_or(defaults.backend))
+            .latest_migration(config.latest_migration.unwrap_or(defaults.latest_migration))
+            .migrations_checksum(
+                config
+                    .migrations_checksum
+                    .unwrap_or(defaults.migrations_checksum),
+            )
+            .generated_at(config.generated_at.unwrap_or(defaults.generated_at))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+struct ExtensionsConfig {
+    keep_create_extension: Option<bool>,
+    drop_extension_comments: Option<bool>,
+    exclude_extension_objects: Option<bool>,
+}
+
+#[cfg(feature = "postgres")]
+impl From<ExtensionsConfig> for crate::ExtensionOptions {
+    fn from(config: ExtensionsConfig) -> Self {
+        let defaults = crate::ExtensionOptions::new();
+        crate::ExtensionOptions::new()
+            .keep_create_extension(
+                config
+                    .keep_create_extension
+                    .unwrap_or(defaults.keep_create_extension),
+            )
+            .drop_extension_comments(
+                config
+                    .drop_extension_comments
+                    .unwrap_or(defaults.drop_extension_comments),
+            )
+            .exclude_extension_objects(
+                config
+                    .exclude_extension_objects
+                    .unwrap_or(defaults.exclude_extension_objects),
+            )
+    }
+}
+
+impl ProjectConfig {
+    /// Look for `database-schema.toml`, or a `Cargo.toml` with a
+    /// `[package.metadata.database-schema]` section, in the current directory and its
+    /// parents.
+    pub fn discover() -> Result<Self, Error> {
+        Self::discover_from(&std::env::current_dir()?)
+    }
+
+    fn discover_from(current_dir: &Path) -> Result<Self, Error> {
+        for dir in current_dir.ancestors() {
+            let config_file = dir.join(CONFIG_FILE_NAME);
+            if config_file.is_file() {
+                return Self::load(config_file);
+            }
+            let manifest = dir.join("Cargo.toml");
+            if manifest.is_file() {
+                if let Some(config) = Self::from_manifest(&manifest)? {
+                    return Ok(config);
+                }
+            }
+        }
+        Err(Error::ConfigError(format!(
+            "no {CONFIG_FILE_NAME} or [package.metadata.database-schema] found from {}",
+            current_dir.display()
+        )))
+    }
+
+    /// Load the configuration from `path`. If it is a `Cargo.toml`, the configuration is
+    /// read from its `[package.metadata.database-schema]` section.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if path.file_name() == Some("Cargo.toml".as_ref()) {
+            return Self::from_manifest(path)?.ok_or_else(|| {
+                Error::ConfigError(format!(
+                    "no [package.metadata.database-schema] section in {}",
+                    path.display()
+                ))
+            });
+        }
+        let contents = std::fs::read_to_string(path)?;
+        let mut config: ProjectConfig =
+            toml::from_str(&contents).map_err(|e| Error::ConfigError(e.to_string()))?;
+        config.root = root_of(path);
+        Ok(config)
+    }
+
+    fn from_manifest(path: &Path) -> Result<Option<Self>, Error> {
+        let manifest: toml::Table = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        let section = manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("database-schema"));
+        let Some(section) = section else {
+            return Ok(None);
+        };
+        let mut config: ProjectConfig = section
+            .clone()
+            .try_into()
+            .map_err(|e: toml::de::Error| Error::ConfigError(e.to_string()))?;
+        config.root = root_of(path);
+        Ok(Some(config))
+    }
+
+    /// Names of all the targets, in alphabetical order.
+    pub fn target_names(&self) -> impl Iterator<Item = &str> {
+        self.targets.keys().map(String::as_str)
+    }
+
+    /// Create a [`DatabaseSchemaBuilder`] configured for the target called `name`.
+    pub fn builder(&self, name: &str) -> Result<DatabaseSchemaBuilder, Error> {
+        let target = self
+            .targets
+            .get(name)
+            .ok_or_else(|| Error::ConfigError(format!("unknown target `{name}`")))?;
+
+        let mut builder = DatabaseSchemaBuilder::new();
+        let connection_url = match (&target.url, &target.url_env) {
+            (Some(url), _) => Some(url.clone()),
+            (None, Some(var)) => Some(std::env::var(var).map_err(|e| {
+                Error::ConfigError(format!("target `{name}`: {var} is not usable: {e}"))
+            })?),
+            (None, None) => None,
+        };
+        if let Some(connection_url) = connection_url {
+            let _ = builder.connection_url(connection_url);
+        }
+
+        let detected = Backend::from_connection_url(&builder.0.connection_url.0);
+        if let Some(ref backend) = target.backend {
+            let backend: Backend = backend.parse()?;
+            if backend != detected {
+                return Err(Error::ConfigError(format!(
+                    "target `{name}` is configured for {backend} but its connection URL is for {detected}"
+                )));
+            }
+        }
+
+        if let Some(ref migrations_dir) = target.migrations_dir {
+            let _ = builder.migrations_dir(self.root.join(migrations_dir))?;
+        }
+        if let Some(ref destination) = target.destination {
+            let _ = builder.destination_path(self.root.join(destination));
+        }
+        if let Some(ref history_dir) = target.history_dir {
+            let _ = builder.history_dir(self.root.join(history_dir));
+        }
+        if let Some(ref target_version) = target.target_version {
+            let _ = builder.target_version(target_version);
+        }
+        if let Some(ref data_tables) = target.data_tables {
+            let _ = builder.data_tables(data_tables);
+        }
+        if let Some(ref exclude_tables) = target.exclude_tables {
+            let _ = builder.exclude_tables(exclude_tables);
+        }
+        if let Some(header) = target.header {
+            let _ = builder.header(header.into());
+        }
+        if let Some(extensions) = target.postgres_extensions {
+            #[cfg(feature = "postgres")]
+            {
+                let _ = builder.postgres_extensions(extensions.into());
+            }
+            #[cfg(not(feature = "postgres"))]
+            {
+                let _ = extensions;
+                return Err(Error::UnsupportedBackend(Backend::Postgres.to_string()));
+            }
+        }
+        Ok(builder)
+    }
+
+    /// Dump the target called `name`.
+    pub async fn dump(&self, name: &str) -> Result<(), Error> {
+        self.builder(name)?.build().dump().await
+    }
+
+    /// Dump every target, one after the other, stopping at the first failure.
+    pub async fn dump_all(&self) -> Result<(), Error> {
+        for name in self.targets.keys() {
+            tracing::info!(target = name, "Dumping database schema");
+            self.dump(name).await?;
+        }
+        Ok(())
+    }
+}
+
+fn root_of(path: &Path) -> PathBuf {
+    path.parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectConfig;
+
+    #[cfg(any(feature = "sqlx", feature = "cli"))]
+    const CONFIG: &str = r#"
+[targets.cache]
+url = "sqlite::memory:"
+migrations_dir = "fixtures/sqlx/sqlite/migrations"
+destination = "cache.sql"
+header = { generated_at = true }
+target_version = "20230827000000"
+data_tables = ["roles"]
+
+[targets.main]
+url = "postgresql://root:@127.0.0.1:5432/postgres"
+backend = "postgres"
+"#;
+    #[cfg(feature = "diesel")]
+    const CONFIG: &str = r#"
+[targets.cache]
+url = ":memory:"
+migrations_dir = "fixtures/diesel/sqlite/migrations"
+destination = "cache.sql"
+header = { generated_at = true }
+target_version = "20230827000000"
+data_tables = ["roles"]
+
+[targets.main]
+url = "postgresql://root:@127.0.0.1:5432/postgres"
+backend = "postgres"
+"#;
+
+    fn load(contents: &str) -> Result<ProjectConfig, crate::Error> {
+        let mut config: ProjectConfig =
+            toml::from_str(contents).map_err(|e| crate::Error::ConfigError(e.to_string()))?;
+        config.root = std::path::PathBuf::from(".");
+        Ok(config)
+    }
+
+    #[test]
+    fn test_targets() -> Result<(), crate::Error> {
+        let config = load(CONFIG)?;
+        assert_eq!(
+            config.target_names().collect::<Vec<&str>>(),
+            vec!["cache", "main"]
+        );
+
+        let builder = config.builder("cache")?;
+        assert_eq!(
+            builder.0.destination_path,
+            std::path::PathBuf::from("./cache.sql")
+        );
+        assert_eq!(
+            builder.0.header,
+            Some(crate::HeaderOptions::new().generated_at(true))
+        );
+        assert_eq!(builder.0.target_version.as_deref(), Some("20230827000000"));
+        assert_eq!(builder.0.data_tables, vec![String::from("roles")]);
+        assert!(config.builder("unknown").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_backend_mismatch() -> Result<(), crate::Error> {
+        let config = load(
+            r#"
+[targets.main]
+url = "mysql://root@localhost/app"
+backend = "postgres"
+"#,
+        )?;
+        assert!(matches!(
+            config.builder("main"),
+            Err(crate::Error::ConfigError(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(load("[targets.main]\nmigration_dir = \"migrations\"\n").is_err());
+    }
+
+    /// Empty directory standing for the root of a project.
+    fn project_dir(name: &str) -> Result<std::path::PathBuf, crate::Error> {
+        let dir = std::env::temp_dir().join(format!("{}-config-{name}", crate::FRAMEWORK));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src"))?;
+        Ok(dir)
+    }
+
+    const TARGET: &str = r#"
+url = "postgresql://root:@127.0.0.1:5432/postgres"
+destination = "structure.sql"
+exclude_tables = ["audit.events"]
+"#;
+
+    #[test]
+    fn test_load_config_file() -> Result<(), crate::Error> {
+        let dir = project_dir("file")?;
+        std::fs::write(
+            dir.join(super::CONFIG_FILE_NAME),
+            format!("[targets.main]{TARGET}"),
+        )?;
+
+        for config in [
+            ProjectConfig::load(dir.join(super::CONFIG_FILE_NAME))?,
+            ProjectConfig::discover_from(&dir.join("src"))?,
+        ] {
+            let builder = config.builder("main")?;
+            assert_eq!(builder.0.destination_path, dir.join("structure.sql"));
+            assert_eq!(builder.0.exclude_tables, vec![String::from("audit.events")]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_manifest() -> Result<(), crate::Error> {
+        let dir = project_dir("manifest")?;
+        let manifest = dir.join("Cargo.toml");
+        std::fs::write(
+            &manifest,
+            format!(
+                "[package]\nname = \"app\"\n\n[package.metadata.database-schema.targets.main]{TARGET}"
+            ),
+        )?;
+
+        for config in [
+            ProjectConfig::load(&manifest)?,
+            ProjectConfig::discover_from(&dir.join("src"))?,
+        ] {
+            assert_eq!(config.target_names().collect::<Vec<&str>>(), vec!["main"]);
+            let builder = config.builder("main")?;
+            assert_eq!(builder.0.destination_path, dir.join("structure.sql"));
+            assert_eq!(builder.0.exclude_tables, vec![String::from("audit.events")]);
+        }
+
+        std::fs::write(&manifest, "[package]\nname = \"app\"\n")?;
+        assert!(matches!(
+            ProjectConfig::load(&manifest),
+            Err(crate::Error::ConfigError(_))
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_dump_exclude_tables() -> Result<(), crate::Error> {
+        let dir = project_dir("exclude-tables")?;
+        std::fs::write(
+            dir.join(super::CONFIG_FILE_NAME),
+            format!(
+                r#"
+[targets.main]
+url = "{}"
+migrations_dir = "{}/fixtures/{}/sqlite/migrations"
+destination = "structure.sql"
+exclude_tables = ["users"]
+"#,
+                crate::sqlite::DEFAULT_CONNECTION_URL,
+                env!("CARGO_MANIFEST_DIR"),
+                crate::FRAMEWORK,
+            ),
+        )?;
+
+        ProjectConfig::load(dir.join(super::CONFIG_FILE_NAME))?
+            .dump("main")
+            .await?;
+        let structure = std::fs::read_to_string(dir.join("structure.sql"))?;
+        assert!(structure.contains("_migrations; Type: table"));
+        assert!(!structure.contains("users"));
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! Reference data dumped along with the schema.
+//!
+//! Lookup tables seeded by the migrations (roles, permissions, feature catalogs...) are
+//! part of the schema as far as the application is concerned. Their rows are appended to
+//! the dump as `INSERT` statements, sorted so that the dump only changes when the data
+//! does: `pg_dump` and `mysqldump` in data-only mode for `postgres` and `mysql`, and a
+//! query on the tables for `sqlite`.
+
+use crate::error::Error;
+
+/// Check that the names of `tables` are plain (possibly qualified) identifiers, since they
+/// end up in queries and command lines as is.
+pub(crate) fn check_tables(tables: &[String]) -> Result<(), Error> {
+    match tables.iter().find(|table| {
+        table.is_empty()
+            || table.starts_with('.')
+            || table.ends_with('.')
+            || !table
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    }) {
+        Some(table) => Err(Error::InvalidDataTable(table.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Sort every run of consecutive `INSERT` statements of `dump`, leaving the other lines
+/// alone.
+///
+/// Statements end with a `;` at the end of a line, outside of a string literal: values
+/// may span several lines. `backslash_escapes` is for the `\'` escapes of `mysqldump`.
+#[cfg_attr(not(any(feature = "mysql", feature = "postgres")), allow(dead_code))]
+pub(crate) fn sort_inserts(dump: &str, backslash_escapes: bool) -> String {
+    let mut output = Vec::new();
+    let mut run: Vec<String> = Vec::new();
+    let mut statement: Option<String> = None;
+    let mut in_string = false;
+
+    for line in dump.lines() {
+        let mut current = match statement.take() {
+            Some(mut current) => {
+                current.push('\n');
+                current
+            }
+            None if line.starts_with("INSERT INTO ") => String::new(),
+            None => {
+                run.sort();
+                output.append(&mut run);
+                output.push(line.to_owned());
+                continue;
+            }
+        };
+        current.push_str(line);
+
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if in_string && backslash_escapes => {
+                    let _ = chars.next();
+                }
+                // A doubled quote inside a string closes and reopens it.
+                '\'' => in_string = !in_string,
+                _ => {}
+            }
+        }
+        match !in_string && line.ends_with(';') {
+            true => run.push(current),
+            false => statement = Some(current),
+        }
+    }
+    run.extend(statement);
+    run.sort();
+    output.append(&mut run);
+
+    let mut sorted = output.join("\n");
+    if dump.ends_with('\n') {
+        sorted.push('\n');
+    }
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_check_tables() {
+        let tables =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert!(super::check_tables(&tables(&["roles", "public.permissions"])).is_ok());
+        assert!(super::check_tables(&tables(&["roles", "users; --"])).is_err());
+        assert!(super::check_tables(&tables(&[""])).is_err());
+        assert!(super::check_tables(&tables(&["public."])).is_err());
+    }
+
+    #[test]
+    fn test_sort_inserts() {
+        let dump = "--
+-- Data for Name: roles; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+INSERT INTO public.roles VALUES (2, 'editor');
+INSERT INTO public.roles VALUES (3, 'multi
+line;
+INSERT INTO not a statement');
+INSERT INTO public.roles VALUES (1, 'admin''s');
+
+SELECT pg_catalog.setval('public.roles_id_seq', 3, true);
+";
+        assert_eq!(
+            super::sort_inserts(dump, false),
+            "--
+-- Data for Name: roles; Type: TABLE DATA; Schema: public; Owner: -
+--
+
+INSERT INTO public.roles VALUES (1, 'admin''s');
+INSERT INTO public.roles VALUES (2, 'editor');
+INSERT INTO public.roles VALUES (3, 'multi
+line;
+INSERT INTO not a statement');
+
+SELECT pg_catalog.setval('public.roles_id_seq', 3, true);
+"
+        );
+    }
+
+    #[test]
+    fn test_sort_inserts_backslash_escapes() {
+        let dump =
+            "INSERT INTO `roles` VALUES (2,'it\\'s;');\nINSERT INTO `roles` VALUES (1,'admin');\n";
+        assert_eq!(
+            super::sort_inserts(dump, true),
+            "INSERT INTO `roles` VALUES (1,'admin');\nINSERT INTO `roles` VALUES (2,'it\\'s;');\n"
+        );
+    }
+}
@@ -0,0 +1,1297 @@
+//! Comparing two schemas, from dumps or from databases as they are.
+//!
+//! A dump is parsed into a [`Schema`]: its tables with their columns and constraints, its
+//! indexes and its views. Databases are dumped first, with the same tools as
+//! [`dump`](crate::DatabaseSchema::dump) but without running any migration, so that a
+//! database changed by hand is compared as it is. Two schemas are then compared into a
+//! [`SchemaDiff`], a list of typed [`SchemaChange`]s.
+//!
+//! Definitions (column types and defaults, constraints, indexes and views) are compared
+//! on their text, with whitespace and identifier quoting normalized: the dumps of a
+//! backend always write them the same way. Other objects (functions, triggers,
+//! sequences...) are left out.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::Backend;
+
+/// Where a schema to compare comes from.
+///
+/// ```rust,ignore
+/// let diff = DatabaseSchemaBuilder::new()
+///     .build()
+///     .diff(
+///         &SchemaSource::file("main/structure.sql"),
+///         &SchemaSource::database("postgresql://localhost/app"),
+///     )
+///     .await?;
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub enum SchemaSource {
+    /// A dump, such as the `structure.sql` of another branch.
+    File(PathBuf),
+
+    /// The database at a connection URL, dumped as it is: no migration is run.
+    Database(String),
+}
+
+impl SchemaSource {
+    /// The dump at `path`.
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        SchemaSource::File(path.as_ref().to_path_buf())
+    }
+
+    /// The database at `connection_url`.
+    pub fn database<S: Into<String>>(connection_url: S) -> Self {
+        SchemaSource::Database(connection_url.into())
+    }
+}
+
+impl std::fmt::Debug for SchemaSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SchemaSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            SchemaSource::Database(connection_url) => f
+                .debug_tuple("Database")
+                .field(&crate::process::redact(connection_url))
+                .finish(),
+        }
+    }
+}
+
+/// The tables, indexes and views of a dump.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Schema {
+    pub(crate) tables: BTreeMap<String, Table>,
+    /// Indexes by table and name.
+    pub(crate) indexes: BTreeMap<(String, String), Index>,
+    pub(crate) views: BTreeMap<String, View>,
+}
+
+/// A table, with its columns in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub(crate) name: String,
+    pub(crate) columns: Vec<Column>,
+    pub(crate) constraints: Vec<Constraint>,
+}
+
+/// A column of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub(crate) name: String,
+    pub(crate) data_type: String,
+    pub(crate) nullable: bool,
+    pub(crate) default: Option<String>,
+    pub(crate) extra: Option<String>,
+}
+
+/// A table constraint: primary key, foreign key, unique or check constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub(crate) name: Option<String>,
+    pub(crate) definition: String,
+}
+
+/// An index of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    pub(crate) name: String,
+    pub(crate) table: String,
+    pub(crate) unique: bool,
+    pub(crate) definition: String,
+}
+
+/// A view, or a materialized view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View {
+    pub(crate) name: String,
+    pub(crate) definition: String,
+}
+
+impl Schema {
+    /// The tables, by name.
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values()
+    }
+
+    /// The table named `name`, qualified as in the dump (`public.users` with `postgres`).
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    /// The indexes, by table and name.
+    pub fn indexes(&self) -> impl Iterator<Item = &Index> {
+        self.indexes.values()
+    }
+
+    /// The views, by name.
+    pub fn views(&self) -> impl Iterator<Item = &View> {
+        self.views.values()
+    }
+
+    /// What changed from this schema to `to`.
+    pub fn diff(&self, to: &Schema) -> SchemaDiff {
+        let mut changes = Vec::new();
+        for pair in pairs(&self.tables, &to.tables) {
+            match pair {
+                (Some(from), None) => changes.push(SchemaChange::TableRemoved(from.clone())),
+                (None, Some(to)) => changes.push(SchemaChange::TableAdded(to.clone())),
+                (Some(from), Some(to)) => from.diff(to, &mut changes),
+                (None, None) => {}
+            }
+        }
+        for pair in pairs(&self.indexes, &to.indexes) {
+            match pair {
+                (Some(from), None) => changes.push(SchemaChange::IndexRemoved(from.clone())),
+                (None, Some(to)) => changes.push(SchemaChange::IndexAdded(to.clone())),
+                (Some(from), Some(to)) if from != to => changes.push(SchemaChange::IndexChanged {
+                    from: from.clone(),
+                    to: to.clone(),
+                }),
+                _ => {}
+            }
+        }
+        for pair in pairs(&self.views, &to.views) {
+            match pair {
+                (Some(from), None) => changes.push(SchemaChange::ViewRemoved(from.clone())),
+                (None, Some(to)) => changes.push(SchemaChange::ViewAdded(to.clone())),
+                (Some(from), Some(to)) if from != to => changes.push(SchemaChange::ViewChanged {
+                    from: from.clone(),
+                    to: to.clone(),
+                }),
+                _ => {}
+            }
+        }
+        SchemaDiff { changes }
+    }
+
+    /// Parse the dump `sql` of `backend`.
+    pub(crate) fn parse(sql: &str, backend: Backend) -> Schema {
+        let mut schema = Schema::default();
+        for statement in crate::sql::split(sql, backend) {
+            schema.apply(&tokens(&statement.tokens, backend), backend);
+        }
+        schema
+    }
+
+    fn apply(&mut self, tokens: &[Token], backend: Backend) {
+        if let Some(rest) = strip(tokens, &["CREATE"]) {
+            let rest = skip(rest, &["OR", "REPLACE"]);
+            let rest = ["TEMPORARY", "TEMP", "UNLOGGED"]
+                .into_iter()
+                .fold(rest, |rest, keyword| skip(rest, &[keyword]));
+            if let Some(rest) = strip(rest, &["TABLE"]) {
+                self.create_table(rest, backend);
+            } else if let Some(rest) = strip(rest, &["UNIQUE", "INDEX"]) {
+                self.create_index(rest, true, backend);
+            } else if let Some(rest) = strip(rest, &["INDEX"]) {
+                self.create_index(rest, false, backend);
+            } else if let Some(view) = rest.iter().position(|token| token.is("VIEW")) {
+                // `mysqldump` puts the algorithm and the definer in between.
+                if !rest[..view]
+                    .iter()
+                    .any(|token| token.is("AS") || token.is_symbol("("))
+                {
+                    self.create_view(&rest[view + 1..], backend);
+                }
+            }
+        } else if let Some(rest) = strip(tokens, &["ALTER", "TABLE"]) {
+            self.alter_table(rest, backend);
+        } else if let Some(rest) = strip(tokens, &["DROP"]) {
+            self.drop(rest);
+        }
+    }
+
+    fn create_table(&mut self, tokens: &[Token], backend: Backend) {
+        let tokens = skip(tokens, &["IF", "NOT", "EXISTS"]);
+        let Some((name, rest)) = qualified_name(tokens) else {
+            return;
+        };
+        let mut table = Table {
+            name: name.clone(),
+            columns: Vec::new(),
+            constraints: Vec::new(),
+        };
+        self.indexes.retain(|(table, _), _| *table != name);
+        if let Some((body, _)) = parenthesized(rest) {
+            for element in split_commas(body) {
+                self.add_element(&mut table, element, backend);
+            }
+        }
+        let _ = self.views.remove(&name);
+        let _ = self.tables.insert(name, table);
+    }
+
+    /// Add a column, a table constraint or an inline `mysql` index to `table`.
+    fn add_element(&mut self, table: &mut Table, tokens: &[Token], backend: Backend) {
+        let Some(first) = tokens.first() else {
+            return;
+        };
+        if first.is("CONSTRAINT") {
+            let Some(name) = tokens.get(1).and_then(Token::identifier) else {
+                return;
+            };
+            table.add_constraint(Constraint {
+                name: Some(name.to_owned()),
+                definition: render(&tokens[2..], backend),
+            });
+            return;
+        }
+
+        if backend == Backend::MySql {
+            let (unique, rest) = match strip(tokens, &["UNIQUE"]) {
+                Some(rest) => (true, rest),
+                None => (false, tokens),
+            };
+            let (kind, rest) = match rest.first() {
+                Some(kind) if kind.is("FULLTEXT") || kind.is("SPATIAL") => {
+                    (render(&rest[..1], backend) + " ", &rest[1..])
+                }
+                _ => (String::new(), rest),
+            };
+            if let Some(rest) = strip(rest, &["KEY"]).or_else(|| strip(rest, &["INDEX"])) {
+                let (name, rest) = match rest.first().and_then(Token::identifier) {
+                    Some(name) => (name.to_owned(), &rest[1..]),
+                    None => (render(rest, backend), rest),
+                };
+                let index = Index {
+                    name: name.clone(),
+                    table: table.name.clone(),
+                    unique,
+                    definition: kind + &render(rest, backend),
+                };
+                let _ = self.indexes.insert((table.name.clone(), name), index);
+                return;
+            }
+        }
+
+        const CONSTRAINTS: [&str; 5] = ["PRIMARY", "UNIQUE", "FOREIGN", "CHECK", "EXCLUDE"];
+        if CONSTRAINTS.iter().any(|keyword| first.is(keyword)) {
+            table.add_constraint(Constraint {
+                name: None,
+                definition: render(tokens, backend),
+            });
+        } else if !first.is("LIKE") {
+            if let Some(column) = column(tokens, backend) {
+                table.columns.retain(|other| other.name != column.name);
+                table.columns.push(column);
+            }
+        }
+    }
+
+    fn create_index(&mut self, tokens: &[Token], unique: bool, backend: Backend) {
+        let tokens = skip(tokens, &["CONCURRENTLY"]);
+        let tokens = skip(tokens, &["IF", "NOT", "EXISTS"]);
+        let Some((name, rest)) = qualified_name(tokens) else {
+            return;
+        };
+        let Some(rest) = strip(rest, &["ON"]) else {
+            return;
+        };
+        let Some((table, rest)) = qualified_name(skip(rest, &["ONLY"])) else {
+            return;
+        };
+        let index = Index {
+            name: name.clone(),
+            table: table.clone(),
+            unique,
+            definition: render(rest, backend),
+        };
+        let _ = self.indexes.insert((table, name), index);
+    }
+
+    fn create_view(&mut self, tokens: &[Token], backend: Backend) {
+        let tokens = skip(tokens, &["IF", "NOT", "EXISTS"]);
+        let Some((name, rest)) = qualified_name(tokens) else {
+            return;
+        };
+        let Some(query) = top_level(rest).find(|(_, token)| token.is("AS")) else {
+            return;
+        };
+        let view = View {
+            name: name.clone(),
+            definition: render(&rest[query.0 + 1..], backend),
+        };
+        // Older `mysqldump` versions stand in for views with tables.
+        let _ = self.tables.remove(&name);
+        let _ = self.views.insert(name, view);
+    }
+
+    fn alter_table(&mut self, tokens: &[Token], backend: Backend) {
+        let tokens = skip(tokens, &["IF", "EXISTS"]);
+        let Some((name, rest)) = qualified_name(skip(tokens, &["ONLY"])) else {
+            return;
+        };
+        let Some(mut table) = self.tables.remove(&name) else {
+            return;
+        };
+        for clause in split_commas(rest) {
+            if let Some(rest) = strip(clause, &["ADD"]) {
+                let rest = skip(rest, &["COLUMN"]);
+                self.add_element(&mut table, skip(rest, &["IF", "NOT", "EXISTS"]), backend);
+            } else if let Some(rest) = strip(clause, &["ALTER"]) {
+                table.alter_column(skip(rest, &["COLUMN"]), backend);
+            } else if let Some(rest) = strip(clause, &["DROP", "CONSTRAINT"]) {
+                if let Some(constraint) = skip(rest, &["IF", "EXISTS"])
+                    .first()
+                    .and_then(Token::identifier)
+                {
+                    table
+                        .constraints
+                        .retain(|other| other.name.as_deref() != Some(constraint));
+                }
+            } else if let Some(rest) = strip(clause, &["DROP"]) {
+                let rest = skip(rest, &["COLUMN"]);
+                if let Some(column) = skip(rest, &["IF", "EXISTS"])
+                    .first()
+                    .and_then(Token::identifier)
+                {
+                    table.columns.retain(|other| other.name != column);
+                }
+            }
+        }
+        let _ = self.tables.insert(name, table);
+    }
+
+    fn drop(&mut self, tokens: &[Token]) {
+        if let Some(rest) = strip(tokens, &["TABLE"]) {
+            for table in split_commas(skip(rest, &["IF", "EXISTS"])) {
+                if let Some((table, _)) = qualified_name(table) {
+                    let _ = self.tables.remove(&table);
+                    self.indexes.retain(|(other, _), _| *other != table);
+                }
+            }
+        } else if let Some(rest) =
+            strip(tokens, &["VIEW"]).or_else(|| strip(tokens, &["MATERIALIZED", "VIEW"]))
+        {
+            for view in split_commas(skip(rest, &["IF", "EXISTS"])) {
+                if let Some((view, _)) = qualified_name(view) {
+                    let _ = self.views.remove(&view);
+                }
+            }
+        } else if let Some(rest) = strip(tokens, &["INDEX"]) {
+            let rest = skip(rest, &["CONCURRENTLY"]);
+            if let Some((index, _)) = qualified_name(skip(rest, &["IF", "EXISTS"])) {
+                self.indexes.retain(|(_, other), _| *other != index);
+            }
+        }
+    }
+}
+
+impl Table {
+    /// Name of the table, qualified as in the dump.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The columns, in order.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The column named `name`.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The constraints, in the order of the dump.
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    fn constraint(&self, key: &str) -> Option<&Constraint> {
+        self.constraints
+            .iter()
+            .find(|constraint| constraint.key() == key)
+    }
+
+    fn add_constraint(&mut self, constraint: Constraint) {
+        self.constraints
+            .retain(|other| other.key() != constraint.key());
+        self.constraints.push(constraint);
+    }
+
+    /// Apply an `ALTER COLUMN` clause.
+    fn alter_column(&mut self, tokens: &[Token], backend: Backend) {
+        let Some(column) = tokens.first().and_then(Token::identifier) else {
+            return;
+        };
+        let Some(column) = self.columns.iter_mut().find(|other| other.name == column) else {
+            return;
+        };
+        let rest = &tokens[1..];
+        if let Some(default) = strip(rest, &["SET", "DEFAULT"]) {
+            column.default = Some(render(default, backend));
+        } else if strip(rest, &["DROP", "DEFAULT"]).is_some() {
+            column.default = None;
+        } else if strip(rest, &["SET", "NOT", "NULL"]).is_some() {
+            column.nullable = false;
+        } else if strip(rest, &["DROP", "NOT", "NULL"]).is_some() {
+            column.nullable = true;
+        } else if let Some(data_type) =
+            strip(rest, &["SET", "DATA", "TYPE"]).or_else(|| strip(rest, &["TYPE"]))
+        {
+            let end = top_level(data_type)
+                .find(|(_, token)| token.is("USING"))
+                .map_or(data_type.len(), |(end, _)| end);
+            column.data_type = render(&data_type[..end], backend);
+        } else if strip(rest, &["ADD", "GENERATED"]).is_some() {
+            // Identity columns of `pg_dump`
+            let generated = render(&rest[1..], backend);
+            column.extra = Some(match column.extra.take() {
+                Some(extra) => format!("{extra} {generated}"),
+                None => generated,
+            });
+        }
+    }
+
+    /// Push the changes from this table to `to` onto `changes`.
+    fn diff(&self, to: &Table, changes: &mut Vec<SchemaChange>) {
+        for column in &self.columns {
+            match to.column(&column.name) {
+                None => changes.push(SchemaChange::ColumnRemoved {
+                    table: self.name.clone(),
+                    column: column.clone(),
+                }),
+                Some(other) if other != column => changes.push(SchemaChange::ColumnChanged {
+                    table: self.name.clone(),
+                    from: column.clone(),
+                    to: other.clone(),
+                }),
+                _ => {}
+            }
+        }
+        for column in &to.columns {
+            if self.column(&column.name).is_none() {
+                changes.push(SchemaChange::ColumnAdded {
+                    table: self.name.clone(),
+                    column: column.clone(),
+                });
+            }
+        }
+
+        for constraint in &self.constraints {
+            match to.constraint(constraint.key()) {
+                None => changes.push(SchemaChange::ConstraintRemoved {
+                    table: self.name.clone(),
+                    constraint: constraint.clone(),
+                }),
+                Some(other) if other != constraint => {
+                    changes.push(SchemaChange::ConstraintChanged {
+                        table: self.name.clone(),
+                        from: constraint.clone(),
+                        to: other.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        for constraint in &to.constraints {
+            if self.constraint(constraint.key()).is_none() {
+                changes.push(SchemaChange::ConstraintAdded {
+                    table: self.name.clone(),
+                    constraint: constraint.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl Column {
+    /// Name of the column.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type of the column, such as `character varying(255)`.
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
+
+    /// Whether the column accepts `NULL`.
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// Default value expression, such as `now()`.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    /// The rest of the definition: inline constraints, `COLLATE`, `AUTO_INCREMENT`,
+    /// identity...
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    /// The definition, without the name.
+    pub(crate) fn definition(&self) -> String {
+        let mut definition = self.data_type.clone();
+        if !self.nullable {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(ref default) = self.default {
+            definition.push_str(&format!(" DEFAULT {default}"));
+        }
+        if let Some(ref extra) = self.extra {
+            definition.push_str(&format!(" {extra}"));
+        }
+        definition
+    }
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.definition())
+    }
+}
+
+impl Constraint {
+    /// Name of the constraint, unless it is unnamed in the dump.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The definition, such as `PRIMARY KEY(id)`.
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    /// Identifies the constraint in its table: its name, or its definition.
+    fn key(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.definition)
+    }
+}
+
+impl Index {
+    /// Name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The indexed table.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Whether the index is unique.
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// What follows the table name, such as `USING btree(email)`.
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+}
+
+impl View {
+    /// Name of the view.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The query of the view.
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+}
+
+/// What changed between two schemas, see [`Schema::diff`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub(crate) changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    /// Whether both schemas are the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes: tables (with their columns and constraints) by name, then indexes,
+    /// then views.
+    pub fn changes(&self) -> &[SchemaChange] {
+        &self.changes
+    }
+}
+
+impl std::fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A change between two schemas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaChange {
+    /// A table was added.
+    TableAdded(Table),
+
+    /// A table was removed.
+    TableRemoved(Table),
+
+    /// A column was added to a table that exists on both sides.
+    ColumnAdded {
+        /// The table of the column.
+        table: String,
+        /// The added column.
+        column: Column,
+    },
+
+    /// A column was removed from a table that exists on both sides.
+    ColumnRemoved {
+        /// The table of the column.
+        table: String,
+        /// The removed column.
+        column: Column,
+    },
+
+    /// The type, default, nullability or other attributes of a column changed.
+    ColumnChanged {
+        /// The table of the column.
+        table: String,
+        /// The column before.
+        from: Column,
+        /// The column after.
+        to: Column,
+    },
+
+    /// A constraint was added to a table that exists on both sides.
+    ConstraintAdded {
+        /// The table of the constraint.
+        table: String,
+        /// The added constraint.
+        constraint: Constraint,
+    },
+
+    /// A constraint was removed from a table that exists on both sides.
+    ConstraintRemoved {
+        /// The table of the constraint.
+        table: String,
+        /// The removed constraint.
+        constraint: Constraint,
+    },
+
+    /// The definition of a named constraint changed.
+    ConstraintChanged {
+        /// The table of the constraint.
+        table: String,
+        /// The constraint before.
+        from: Constraint,
+        /// The constraint after.
+        to: Constraint,
+    },
+
+    /// An index was added.
+    IndexAdded(Index),
+
+    /// An index was removed.
+    IndexRemoved(Index),
+
+    /// The definition of an index changed.
+    IndexChanged {
+        /// The index before.
+        from: Index,
+        /// The index after.
+        to: Index,
+    },
+
+    /// A view was added.
+    ViewAdded(View),
+
+    /// A view was removed.
+    ViewRemoved(View),
+
+    /// The query of a view changed.
+    ViewChanged {
+        /// The view before.
+        from: View,
+        /// The view after.
+        to: View,
+    },
+}
+
+impl std::fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn constraint(table: &str, constraint: &Constraint) -> String {
+            match constraint.name {
+                Some(ref name) => format!("constraint {name} on {table}"),
+                None => format!("constraint on {table}"),
+            }
+        }
+        fn index(index: &Index) -> String {
+            match index.unique {
+                true => format!("UNIQUE {}", index.definition),
+                false => index.definition.clone(),
+            }
+        }
+
+        match self {
+            SchemaChange::TableAdded(table) => write!(f, "added table {}", table.name),
+            SchemaChange::TableRemoved(table) => write!(f, "removed table {}", table.name),
+            SchemaChange::ColumnAdded { table, column } => write!(
+                f,
+                "added column {table}.{}: {}",
+                column.name,
+                column.definition()
+            ),
+            SchemaChange::ColumnRemoved { table, column } => {
+                write!(f, "removed column {table}.{}", column.name)
+            }
+            SchemaChange::ColumnChanged { table, from, to } => {
+                let mut details = Vec::new();
+                if from.data_type != to.data_type {
+                    details.push(format!("type {} -> {}", from.data_type, to.data_type));
+                }
+                if from.nullable != to.nullable {
+                    details.push(String::from(match to.nullable {
+                        true => "NOT NULL -> NULL",
+                        false => "NULL -> NOT NULL",
+                    }));
+                }
+                let or_none =
+                    |value: &Option<String>| value.as_deref().unwrap_or("none").to_owned();
+                if from.default != to.default {
+                    details.push(format!(
+                        "default {} -> {}",
+                        or_none(&from.default),
+                        or_none(&to.default)
+                    ));
+                }
+                if from.extra != to.extra {
+                    details.push(format!(
+                        "{} -> {}",
+                        or_none(&from.extra),
+                        or_none(&to.extra)
+                    ));
+                }
+                write!(
+                    f,
+                    "changed column {table}.{}: {}",
+                    to.name,
+                    details.join(", ")
+                )
+            }
+            SchemaChange::ConstraintAdded {
+                table,
+                constraint: added,
+            } => write!(
+                f,
+                "added {}: {}",
+                constraint(table, added),
+                added.definition
+            ),
+            SchemaChange::ConstraintRemoved {
+                table,
+                constraint: removed,
+            } => write!(f, "removed {}", constraint(table, removed)),
+            SchemaChange::ConstraintChanged { table, from, to } => write!(
+                f,
+                "changed {}: {} -> {}",
+                constraint(table, to),
+                from.definition,
+                to.definition
+            ),
+            SchemaChange::IndexAdded(added) => write!(
+                f,
+                "added index {} on {}: {}",
+                added.name,
+                added.table,
+                index(added)
+            ),
+            SchemaChange::IndexRemoved(removed) => {
+                write!(f, "removed index {} on {}", removed.name, removed.table)
+            }
+            SchemaChange::IndexChanged { from, to } => write!(
+                f,
+                "changed index {} on {}: {} -> {}",
+                to.name,
+                to.table,
+                index(from),
+                index(to)
+            ),
+            SchemaChange::ViewAdded(view) => write!(f, "added view {}", view.name),
+            SchemaChange::ViewRemoved(view) => write!(f, "removed view {}", view.name),
+            SchemaChange::ViewChanged { to, .. } => write!(f, "changed view {}", to.name),
+        }
+    }
+}
+
+/// The values of `from` and `to` for every key of either, by key.
+fn pairs<'a, K: Ord, V>(
+    from: &'a BTreeMap<K, V>,
+    to: &'a BTreeMap<K, V>,
+) -> impl Iterator<Item = (Option<&'a V>, Option<&'a V>)> {
+    from.keys()
+        .chain(to.keys())
+        .collect::<BTreeSet<&K>>()
+        .into_iter()
+        .map(move |key| (from.get(key), to.get(key)))
+}
+
+/// Parse a column definition.
+fn column(tokens: &[Token], backend: Backend) -> Option<Column> {
+    /// Keywords starting an attribute, which end the type and the default of a column.
+    const ATTRIBUTES: [&str; 16] = [
+        "NOT",
+        "NULL",
+        "DEFAULT",
+        "PRIMARY",
+        "UNIQUE",
+        "REFERENCES",
+        "CHECK",
+        "CONSTRAINT",
+        "COLLATE",
+        "GENERATED",
+        "AUTO_INCREMENT",
+        "AUTOINCREMENT",
+        "COMMENT",
+        "ON",
+        "VISIBLE",
+        "INVISIBLE",
+    ];
+    let is_attribute = |token: &Token| ATTRIBUTES.iter().any(|keyword| token.is(keyword));
+
+    let name = tokens.first()?.identifier()?.to_owned();
+    let rest = &tokens[1..];
+    // The first word is always part of the type, as in `character varying`.
+    let type_end = top_level(rest)
+        .skip(1)
+        .find(|(_, token)| {
+            is_attribute(token)
+                || ["CHARACTER", "CHARSET", "AS", "SRID"]
+                    .iter()
+                    .any(|keyword| token.is(keyword))
+        })
+        .map_or(rest.len(), |(end, _)| end);
+
+    let mut column = Column {
+        name,
+        data_type: render(&rest[..type_end], backend),
+        nullable: true,
+        default: None,
+        extra: None,
+    };
+    let mut extra = Vec::new();
+    let mut depth = 0;
+    let mut i = type_end;
+    while i < rest.len() {
+        let token = &rest[i];
+        // `ON DELETE SET NULL`, `GENERATED BY DEFAULT`...
+        let is_column_attribute =
+            depth == 0 && !(i > type_end && (rest[i - 1].is("SET") || rest[i - 1].is("BY")));
+        if is_column_attribute
+            && token.is("NOT")
+            && rest.get(i + 1).is_some_and(|next| next.is("NULL"))
+        {
+            column.nullable = false;
+            i += 2;
+        } else if is_column_attribute && token.is("NULL") {
+            column.nullable = true;
+            i += 1;
+        } else if is_column_attribute && token.is("DEFAULT") {
+            let end = top_level(&rest[i + 1..])
+                .skip(1)
+                .find(|(_, token)| is_attribute(token))
+                .map_or(rest.len(), |(end, _)| i + 1 + end);
+            column.default = Some(render(&rest[i + 1..end], backend));
+            i = end;
+        } else {
+            if token.is_symbol("(") {
+                depth += 1;
+            } else if token.is_symbol(")") {
+                depth -= 1;
+            }
+            extra.push(token.clone());
+            i += 1;
+        }
+    }
+    column.extra = (!extra.is_empty()).then(|| render(&extra, backend));
+    Some(column)
+}
+
+/// A token of a statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// Keyword, unquoted identifier or number.
+    Word(String),
+    /// Quoted identifier, without its quotes.
+    Quoted(String),
+    /// String literal or dollar-quoted body, as written.
+    Literal(String),
+    /// Punctuation or operator.
+    Symbol(String),
+}
+
+impl Token {
+    pub(crate) fn is(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    pub(crate) fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self, Token::Symbol(other) if other == symbol)
+    }
+
+    /// The identifier, for words and quoted identifiers.
+    pub(crate) fn identifier(&self) -> Option<&str> {
+        match self {
+            Token::Word(identifier) | Token::Quoted(identifier) => Some(identifier),
+            _ => None,
+        }
+    }
+}
+
+/// Split a statement into tokens, dropping comments but keeping the code of the
+/// conditional comments of `mysqldump`.
+pub(crate) fn tokenize(sql: &str, backend: Backend) -> Vec<Token> {
+    tokens(&crate::sql::tokenize(sql, backend), backend)
+}
+
+/// The tokens of a statement, from the ones of [`crate::sql`]: operators are read whole.
+pub(crate) fn tokens(sql_tokens: &[crate::sql::Token], backend: Backend) -> Vec<Token> {
+    use crate::sql::TokenKind;
+    const OPERATORS: &str = "+-*/<>=~!@#%^&|?";
+
+    let is_operator = |token: &crate::sql::Token| {
+        token.kind == TokenKind::Punctuation && OPERATORS.contains(token.text)
+    };
+    let mut tokens = Vec::new();
+    let mut previous = None;
+    for token in sql_tokens {
+        match token.kind {
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment => {}
+            TokenKind::ConditionalComment => {
+                let code =
+                    token.text["/*!".len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+                tokens.extend(tokenize(code.strip_suffix("*/").unwrap_or(code), backend));
+            }
+            TokenKind::Word => tokens.push(Token::Word(token.text.to_owned())),
+            TokenKind::String => tokens.push(Token::Literal(token.text.to_owned())),
+            TokenKind::QuotedIdentifier => {
+                let quote = &token.text[..1];
+                let identifier = token.text.get(1..token.text.len() - 1).unwrap_or_default();
+                tokens.push(Token::Quoted(match quote {
+                    "[" => identifier.to_owned(),
+                    _ => identifier.replace(&quote.repeat(2), quote),
+                }));
+            }
+            TokenKind::Punctuation => {
+                let glued = previous.is_some_and(|previous: &crate::sql::Token| {
+                    (is_operator(previous) && is_operator(token))
+                        || (previous.text == ":" && token.text == ":")
+                });
+                match tokens.last_mut() {
+                    Some(Token::Symbol(symbol)) if glued && symbol != "::" => {
+                        symbol.push_str(token.text)
+                    }
+                    _ => tokens.push(Token::Symbol(token.text.to_owned())),
+                }
+            }
+        }
+        previous = Some(token);
+    }
+    tokens
+}
+
+/// Render `tokens` as normalized text: single spaces, none inside parentheses or around
+/// `.` and `::`, nor before the parenthesis of a function call or type.
+pub(crate) fn render(tokens: &[Token], backend: Backend) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        if let Some(previous) = previous {
+            let glued = [")", ",", ".", "::", "[", "]"]
+                .iter()
+                .any(|symbol| token.is_symbol(symbol))
+                || ["(", ".", "::", "["]
+                    .iter()
+                    .any(|symbol| previous.is_symbol(symbol))
+                || (token.is_symbol("(")
+                    && (previous.identifier().is_some() || previous.is_symbol(")")));
+            if !glued {
+                text.push(' ');
+            }
+        }
+        match token {
+            Token::Word(text_) | Token::Literal(text_) | Token::Symbol(text_) => {
+                text.push_str(text_)
+            }
+            Token::Quoted(identifier) => text.push_str(&quote(identifier, backend)),
+        }
+        previous = Some(token);
+    }
+    text
+}
+
+/// Quote `identifier` for `backend`.
+pub(crate) fn quote(identifier: &str, backend: Backend) -> String {
+    let quote = match backend {
+        Backend::MySql => '`',
+        Backend::Postgres | Backend::Sqlite => '"',
+    };
+    format!(
+        "{quote}{}{quote}",
+        identifier.replace(quote, &format!("{quote}{quote}"))
+    )
+}
+
+/// `tokens` without the leading `keywords`, if it starts with them.
+pub(crate) fn strip<'a>(tokens: &'a [Token], keywords: &[&str]) -> Option<&'a [Token]> {
+    (tokens.len() >= keywords.len()
+        && tokens
+            .iter()
+            .zip(keywords)
+            .all(|(token, keyword)| token.is(keyword)))
+    .then(|| &tokens[keywords.len()..])
+}
+
+/// `tokens` without the leading `keywords` if it starts with them, as is otherwise.
+pub(crate) fn skip<'a>(tokens: &'a [Token], keywords: &[&str]) -> &'a [Token] {
+    strip(tokens, keywords).unwrap_or(tokens)
+}
+
+/// Split a possibly qualified name (`public.users`) from the tokens following it.
+pub(crate) fn qualified_name(tokens: &[Token]) -> Option<(String, &[Token])> {
+    let mut name = tokens.first()?.identifier()?.to_owned();
+    let mut rest = &tokens[1..];
+    while let [dot, part, after @ ..] = rest {
+        let Some(part) = part.identifier().filter(|_| dot.is_symbol(".")) else {
+            break;
+        };
+        name.push('.');
+        name.push_str(part);
+        rest = after;
+    }
+    Some((name, rest))
+}
+
+/// The tokens of `tokens` outside of parentheses, with their index.
+pub(crate) fn top_level(tokens: &[Token]) -> impl Iterator<Item = (usize, &Token)> {
+    let mut depth = 0;
+    tokens.iter().enumerate().filter(move |(_, token)| {
+        let is_top_level = depth == 0;
+        if token.is_symbol("(") || token.is_symbol("[") {
+            depth += 1;
+        } else if token.is_symbol(")") || token.is_symbol("]") {
+            depth -= 1;
+        }
+        is_top_level && !token.is_symbol(")") && !token.is_symbol("]")
+    })
+}
+
+/// The inside of the parentheses `tokens` starts with, and the tokens after them.
+pub(crate) fn parenthesized(tokens: &[Token]) -> Option<(&[Token], &[Token])> {
+    if !tokens.first()?.is_symbol("(") {
+        return None;
+    }
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some((&tokens[1..index], &tokens[index + 1..]));
+            }
+        }
+    }
+    None
+}
+
+/// Split `tokens` on the commas outside of parentheses.
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (index, token) in top_level(tokens) {
+        if token.is_symbol(",") {
+            parts.push(&tokens[start..index]);
+            start = index + 1;
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+    use crate::Backend;
+
+    #[test]
+    fn test_parse_postgres() -> Result<(), crate::error::Error> {
+        let dump = std::fs::read_to_string("./fixtures/sqlx/postgres/sqlx-postgres-structure.sql")?;
+        let schema = Schema::parse(&dump, Backend::Postgres);
+        assert_eq!(
+            schema
+                .tables()
+                .map(|table| table.name())
+                .collect::<Vec<&str>>(),
+            vec!["public._sqlx_migrations", "public.sqlx_users"]
+        );
+        let users = schema.table("public.sqlx_users").expect("users table");
+        assert_eq!(
+            users
+                .columns()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "id character varying(32) NOT NULL",
+                "email text NOT NULL",
+                "created_at timestamp without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP"
+            ]
+        );
+        assert_eq!(users.constraints()[0].name(), Some("sqlx_users_pkey"));
+        assert_eq!(users.constraints()[0].definition(), "PRIMARY KEY(id)");
+
+        let schema = Schema::parse(
+            "CREATE TABLE public.posts (id bigint NOT NULL, \"Title\" text, body text);
+ALTER TABLE ONLY public.posts ALTER COLUMN id SET DEFAULT nextval('public.posts_id_seq'::regclass);
+ALTER TABLE public.posts DROP COLUMN body;
+CREATE UNIQUE INDEX posts_title ON public.posts USING btree (\"Title\") WHERE (id > 0);
+CREATE VIEW public.titles AS
+ SELECT posts.\"Title\"
+   FROM public.posts;
+CREATE FUNCTION public.f() RETURNS text LANGUAGE sql AS $$ CREATE TABLE x (id int) $$;",
+            Backend::Postgres,
+        );
+        let posts = schema.table("public.posts").expect("posts table");
+        assert_eq!(
+            posts.column("id").and_then(|column| column.default()),
+            Some("nextval('public.posts_id_seq'::regclass)")
+        );
+        assert_eq!(
+            posts
+                .columns()
+                .iter()
+                .map(|column| column.name())
+                .collect::<Vec<&str>>(),
+            vec!["id", "Title"]
+        );
+        let index = schema.indexes().next().expect("index");
+        assert!(index.is_unique());
+        assert_eq!(index.definition(), "USING btree(\"Title\") WHERE(id > 0)");
+        assert_eq!(
+            schema.views().next().map(|view| view.definition()),
+            Some("SELECT posts.\"Title\" FROM public.posts")
+        );
+        assert!(schema.table("x").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_mysql() {
+        let dump = "/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+DROP TABLE IF EXISTS `users`;
+CREATE TABLE `users` (
+  `id` bigint unsigned NOT NULL AUTO_INCREMENT,
+  `email` varchar(255) COLLATE utf8mb4_unicode_ci NOT NULL,
+  `bio` text,
+  `updated_at` timestamp NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `users_email` (`email`),
+  FULLTEXT KEY `users_bio` (`bio`),
+  CONSTRAINT `users_org` FOREIGN KEY (`org_id`) REFERENCES `orgs` (`id`) ON DELETE SET NULL
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+/*!50001 DROP VIEW IF EXISTS `emails`*/;
+/*!50001 CREATE VIEW `emails` AS SELECT 1 AS `email`*/;
+/*!50001 DROP VIEW IF EXISTS `emails`*/;
+/*!50001 CREATE ALGORITHM=UNDEFINED */
+/*!50013 DEFINER=`root`@`%` SQL SECURITY DEFINER */
+/*!50001 VIEW `emails` AS select `users`.`email` AS `email` from `users` */;
+";
+        let schema = Schema::parse(dump, Backend::MySql);
+        let users = schema.table("users").expect("users table");
+        assert_eq!(
+            users
+                .columns()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "id bigint unsigned NOT NULL AUTO_INCREMENT",
+                "email varchar(255) NOT NULL COLLATE utf8mb4_unicode_ci",
+                "bio text",
+                "updated_at timestamp DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP"
+            ]
+        );
+        assert_eq!(
+            users
+                .constraints()
+                .iter()
+                .map(|constraint| constraint.definition())
+                .collect::<Vec<&str>>(),
+            vec![
+                "PRIMARY KEY(`id`)",
+                "FOREIGN KEY(`org_id`) REFERENCES `orgs`(`id`) ON DELETE SET NULL"
+            ]
+        );
+        assert_eq!(
+            schema
+                .indexes()
+                .map(|index| (index.name(), index.is_unique(), index.definition()))
+                .collect::<Vec<(&str, bool, &str)>>(),
+            vec![
+                ("users_bio", false, "FULLTEXT (`bio`)"),
+                ("users_email", true, "(`email`)")
+            ]
+        );
+        assert_eq!(
+            schema.views().next().map(|view| view.definition()),
+            Some("select `users`.`email` AS `email` from `users`")
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = Schema::parse(
+            "CREATE TABLE users (
+  id TEXT PRIMARY KEY NOT NULL,
+  email TEXT NOT NULL,
+  legacy_id INTEGER,
+  UNIQUE (email)
+);
+CREATE TABLE sessions (id TEXT);
+CREATE INDEX users_email ON users (email);
+CREATE VIEW emails AS SELECT email FROM users;",
+            Backend::Sqlite,
+        );
+        let to = Schema::parse(
+            "CREATE TABLE users (
+  id TEXT PRIMARY KEY NOT NULL,
+  email VARCHAR(255),
+  locale TEXT NOT NULL DEFAULT 'en',
+  CONSTRAINT users_locale CHECK (locale <> '')
+);
+CREATE TABLE posts (id TEXT, user_id TEXT REFERENCES users (id) ON DELETE SET NULL);
+CREATE UNIQUE INDEX users_email ON users (email);
+CREATE VIEW emails AS SELECT email FROM users;",
+            Backend::Sqlite,
+        );
+        assert!(from.diff(&from).is_empty());
+        assert_eq!(
+            from.diff(&to).to_string(),
+            "added table posts
+removed table sessions
+changed column users.email: type TEXT -> VARCHAR(255), NOT NULL -> NULL
+removed column users.legacy_id
+added column users.locale: TEXT NOT NULL DEFAULT 'en'
+removed constraint on users
+added constraint users_locale on users: CHECK(locale <> '')
+changed index users_email on users: (email) -> UNIQUE (email)
+"
+        );
+        let posts = to.table("posts").expect("posts table");
+        assert_eq!(
+            posts.column("user_id").and_then(|column| column.extra()),
+            Some("REFERENCES users(id) ON DELETE SET NULL")
+        );
+    }
+}
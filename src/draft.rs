@@ -0,0 +1,696 @@
+//! Drafting a migration from the difference between the schema of the migrations and a
+//! target schema.
+//!
+//! The migrations are run against a scratch database, whose schema is dumped (without the
+//! migrations table) and compared with the target, a hand-edited dump or a database
+//! changed by hand (see [`diff`](crate::diff)). Every [`SchemaChange`] is turned into the
+//! `CREATE`, `ALTER` or `DROP` statements of the backend, ordered so that views and
+//! indexes are dropped before the tables and columns they use, and created after them.
+//! The down migration is drafted the same way from the reverse difference, so it restores
+//! the schema but not the data that the up migration dropped.
+//!
+//! Some changes can't be written in SQL, such as altering a column with SQLite: they are
+//! left as `TODO` comments in the migration, and listed in [`DraftMigration::manual`].
+
+use std::path::PathBuf;
+
+use crate::{
+    diff::{Column, Constraint, Index, Schema, SchemaChange, SchemaDiff, Table, View},
+    error::Error,
+    migrations::{version_order, MigrationSource},
+    Backend,
+};
+
+/// Name and version of the migration to draft.
+///
+/// ```rust,ignore
+/// let draft = DatabaseSchemaBuilder::new()
+///     .build()
+///     .draft_migration(&SchemaSource::file("structure.sql"), &DraftOptions::new("add_roles"))
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftOptions {
+    pub(crate) name: String,
+    pub(crate) version: Option<String>,
+}
+
+impl DraftOptions {
+    /// Draft a migration named `name`, after its version.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+        }
+    }
+
+    /// Version of the migration, as written in its file or directory name. Defaults to
+    /// the current UTC time, as `20240101123000` (`2024-01-01-123000` with `diesel`).
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+}
+
+/// Result of [`DatabaseSchema::draft_migration`](crate::DatabaseSchema::draft_migration).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftMigration {
+    pub(crate) version: String,
+    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) up: String,
+    pub(crate) down: String,
+    pub(crate) manual: Vec<SchemaChange>,
+}
+
+impl DraftMigration {
+    /// Version of the migration, as written in its file or directory name.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The files written: the up and down migrations, or only the up migration for plain
+    /// migrations, which can't be reverted.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// SQL of the up migration.
+    pub fn up(&self) -> &str {
+        &self.up
+    }
+
+    /// SQL of the down migration.
+    pub fn down(&self) -> &str {
+        &self.down
+    }
+
+    /// Changes of the up migration that couldn't be written in SQL, left as `TODO`
+    /// comments.
+    pub fn manual(&self) -> &[SchemaChange] {
+        &self.manual
+    }
+}
+
+/// How the migrations directory lays out migrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// `<version>_<name>.up.sql` and `<version>_<name>.down.sql`
+    Sqlx,
+    /// `<version>_<name>/up.sql` and `<version>_<name>/down.sql`
+    Diesel,
+    /// `<version>_<name>.sql`, without a down migration
+    Plain,
+}
+
+/// Where the migration goes, found before connecting to anything.
+#[derive(Debug)]
+pub(crate) struct Plan {
+    dir: PathBuf,
+    version: String,
+    layout: Layout,
+}
+
+/// Check `options` against the migrations directory of `migrations`.
+pub(crate) fn plan(migrations: &MigrationSource, options: &DraftOptions) -> Result<Plan, Error> {
+    if options.name.is_empty()
+        || !options
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(Error::MigrationSourceError(format!(
+            "invalid migration name `{}`",
+            options.name
+        )));
+    }
+    let (dir, layout) = match migrations {
+        MigrationSource::Directory(dir) if crate::FRAMEWORK == "diesel" => (dir, Layout::Diesel),
+        MigrationSource::Directory(dir) => (dir, Layout::Sqlx),
+        MigrationSource::Plain(plain) => (&plain.dir, Layout::Plain),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(Error::MigrationSourceError(String::from(
+                "embedded migrations can't be written to, draft into their directory instead",
+            )))
+        }
+    };
+
+    let version = match options.version {
+        Some(ref version) => version.clone(),
+        None if layout == Layout::Diesel => {
+            chrono::Utc::now().format("%Y-%m-%d-%H%M%S").to_string()
+        }
+        None => chrono::Utc::now().format("%Y%m%d%H%M%S").to_string(),
+    };
+    let file_name = format!("{version}_{}.sql", options.name);
+    let normalized = match layout {
+        Layout::Plain => crate::plain::version_from_name(&file_name),
+        _ => crate::migrations::version_from_name(&file_name),
+    }
+    .ok_or_else(|| Error::MigrationSourceError(format!("invalid migration version `{version}`")))?;
+    if let Some(last) = migrations.versions()?.last() {
+        if version_order(&normalized) <= version_order(last) {
+            return Err(Error::MigrationSourceError(format!(
+                "version {version} isn't after the last migration, {last}"
+            )));
+        }
+    }
+    Ok(Plan {
+        dir: dir.clone(),
+        version,
+        layout,
+    })
+}
+
+/// Draft the migration from `current` to `target` and write it where `plan` says. Returns
+/// `None` when the schemas don't differ.
+pub(crate) fn write(
+    plan: Plan,
+    options: &DraftOptions,
+    current: &Schema,
+    target: &Schema,
+    backend: Backend,
+) -> Result<Option<DraftMigration>, Error> {
+    let diff = current.diff(target);
+    if diff.is_empty() {
+        tracing::info!("The migrations already match the target schema");
+        return Ok(None);
+    }
+    let (up, manual) = statements(&diff, backend);
+    let (down, _) = statements(&target.diff(current), backend);
+    let up = format!("-- Drafted by database-schema, review it before running it.\n\n{up}");
+    let down = format!(
+        "-- Drafted by database-schema: this restores the schema, not the dropped data.\n\n{down}"
+    );
+
+    let stem = format!("{}_{}", plan.version, options.name);
+    let paths = match plan.layout {
+        Layout::Sqlx => vec![
+            plan.dir.join(format!("{stem}.up.sql")),
+            plan.dir.join(format!("{stem}.down.sql")),
+        ],
+        Layout::Diesel => vec![
+            plan.dir.join(&stem).join("up.sql"),
+            plan.dir.join(&stem).join("down.sql"),
+        ],
+        Layout::Plain => vec![plan.dir.join(format!("{stem}.sql"))],
+    };
+    if let Some(path) = paths.iter().find(|path| path.exists()) {
+        return Err(Error::MigrationSourceError(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+    if plan.layout == Layout::Diesel {
+        std::fs::create_dir(plan.dir.join(&stem))?;
+    }
+    for (path, sql) in paths.iter().zip([&up, &down]) {
+        std::fs::write(path, sql)?;
+    }
+    tracing::info!(
+        version = plan.version,
+        path = %paths[0].display(),
+        changes = diff.changes.len(),
+        manual = manual.len(),
+        "Wrote draft migration"
+    );
+    Ok(Some(DraftMigration {
+        version: plan.version,
+        paths,
+        up,
+        down,
+        manual,
+    }))
+}
+
+/// When a statement runs, relative to the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    DropViews,
+    DropIndexes,
+    DropConstraints,
+    DropColumns,
+    DropTables,
+    CreateSequences,
+    CreateTables,
+    AlterColumns,
+    AddConstraints,
+    CreateIndexes,
+    CreateViews,
+}
+
+/// The statements of a migration, before they are put in order.
+struct Script {
+    backend: Backend,
+    statements: Vec<(Step, String)>,
+    manual: Vec<SchemaChange>,
+}
+
+/// The SQL applying `diff`, and the changes it can't apply.
+pub(crate) fn statements(diff: &SchemaDiff, backend: Backend) -> (String, Vec<SchemaChange>) {
+    let mut script = Script {
+        backend,
+        statements: Vec::new(),
+        manual: Vec::new(),
+    };
+    for change in &diff.changes {
+        script.change(change);
+    }
+    script.statements.sort_by_key(|(step, _)| *step);
+    let mut sql = String::new();
+    for (_, statement) in script.statements {
+        sql.push_str(&statement);
+        sql.push('\n');
+    }
+    (sql, script.manual)
+}
+
+impl Script {
+    fn push(&mut self, step: Step, statement: String) {
+        self.statements.push((step, format!("{statement};")));
+    }
+
+    /// Leave a `TODO` for `change`, which can't be written with this backend.
+    fn manual(&mut self, step: Step, change: &SchemaChange) {
+        self.statements
+            .push((step, format!("-- TODO ({}): {change}", self.backend)));
+        self.manual.push(change.clone());
+    }
+
+    /// A possibly qualified name, quoted.
+    fn name(&self, name: &str) -> String {
+        name.split('.')
+            .map(|part| crate::diff::quote(part, self.backend))
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    fn change(&mut self, change: &SchemaChange) {
+        match change {
+            SchemaChange::TableAdded(table) => self.create_table(table),
+            SchemaChange::TableRemoved(table) => {
+                let statement = format!("DROP TABLE {}", self.name(&table.name));
+                self.push(Step::DropTables, statement)
+            }
+            SchemaChange::ColumnAdded { table, column } => {
+                self.create_sequence(table, column);
+                let statement = format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    self.name(table),
+                    self.name(&column.name),
+                    column.definition()
+                );
+                self.push(Step::AlterColumns, statement)
+            }
+            SchemaChange::ColumnRemoved { table, column } => {
+                let statement = format!(
+                    "ALTER TABLE {} DROP COLUMN {}",
+                    self.name(table),
+                    self.name(&column.name)
+                );
+                self.push(Step::DropColumns, statement)
+            }
+            SchemaChange::ColumnChanged { table, from, to } => {
+                self.alter_column(table, from, to, change)
+            }
+            SchemaChange::ConstraintAdded { table, constraint } => {
+                self.add_constraint(table, constraint, change)
+            }
+            SchemaChange::ConstraintRemoved { table, constraint } => {
+                self.drop_constraint(table, constraint, change)
+            }
+            SchemaChange::ConstraintChanged { table, from, to } => {
+                self.drop_constraint(table, from, change);
+                self.add_constraint(table, to, change);
+            }
+            SchemaChange::IndexAdded(index) => self.create_index(index),
+            SchemaChange::IndexRemoved(index) => self.drop_index(index),
+            SchemaChange::IndexChanged { from, to } => {
+                self.drop_index(from);
+                self.create_index(to);
+            }
+            SchemaChange::ViewAdded(view) => self.create_view(view),
+            SchemaChange::ViewRemoved(view) => self.drop_view(view),
+            SchemaChange::ViewChanged { from, to } => {
+                self.drop_view(from);
+                self.create_view(to);
+            }
+        }
+    }
+
+    fn create_table(&mut self, table: &Table) {
+        let mut elements = Vec::new();
+        for column in &table.columns {
+            self.create_sequence(&table.name, column);
+            elements.push(format!(
+                "{} {}",
+                self.name(&column.name),
+                column.definition()
+            ));
+        }
+        for constraint in &table.constraints {
+            // Foreign keys may reference tables created after this one. SQLite doesn't
+            // check them when creating a table, and can't add them later.
+            if self.backend != Backend::Sqlite && constraint.definition.starts_with("FOREIGN") {
+                let statement = format!(
+                    "ALTER TABLE {} ADD {}",
+                    self.name(&table.name),
+                    self.constraint(constraint)
+                );
+                self.push(Step::AddConstraints, statement);
+            } else {
+                elements.push(self.constraint(constraint));
+            }
+        }
+        let statement = format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            self.name(&table.name),
+            elements.join(",\n    ")
+        );
+        self.push(Step::CreateTables, statement);
+    }
+
+    /// Create the sequence of a `postgres` `serial` column, which `pg_dump` writes as a
+    /// `nextval()` default, and have the column own it so that it is dropped with it.
+    fn create_sequence(&mut self, table: &str, column: &Column) {
+        if self.backend != Backend::Postgres {
+            return;
+        }
+        let Some(sequence) = column
+            .default
+            .as_deref()
+            .and_then(|default| default.strip_prefix("nextval('"))
+            .and_then(|default| default.strip_suffix("'::regclass)"))
+        else {
+            return;
+        };
+        let sequence = sequence.replace("''", "'");
+        self.push(
+            Step::CreateSequences,
+            format!("CREATE SEQUENCE IF NOT EXISTS {sequence}"),
+        );
+        let statement = format!(
+            "ALTER SEQUENCE {sequence} OWNED BY {}.{}",
+            self.name(table),
+            self.name(&column.name)
+        );
+        self.push(Step::AddConstraints, statement);
+    }
+
+    fn alter_column(&mut self, table: &str, from: &Column, to: &Column, change: &SchemaChange) {
+        let column = self.name(&to.name);
+        let clauses = match self.backend {
+            Backend::MySql => vec![format!("MODIFY COLUMN {column} {}", to.definition())],
+            Backend::Postgres if from.extra == to.extra => {
+                let mut clauses = Vec::new();
+                if from.default.is_some() && from.default != to.default {
+                    clauses.push(format!("ALTER COLUMN {column} DROP DEFAULT"));
+                }
+                if from.data_type != to.data_type {
+                    clauses.push(format!("ALTER COLUMN {column} TYPE {}", to.data_type));
+                }
+                if from.nullable != to.nullable {
+                    let action = if to.nullable { "DROP" } else { "SET" };
+                    clauses.push(format!("ALTER COLUMN {column} {action} NOT NULL"));
+                }
+                if let Some(ref default) =
+                    to.default.as_ref().filter(|_| from.default != to.default)
+                {
+                    self.create_sequence(table, to);
+                    clauses.push(format!("ALTER COLUMN {column} SET DEFAULT {default}"));
+                }
+                clauses
+            }
+            // SQLite can't alter columns, and identities or generated columns don't
+            // change in place.
+            _ => return self.manual(Step::AlterColumns, change),
+        };
+        let statement = format!("ALTER TABLE {} {}", self.name(table), clauses.join(", "));
+        self.push(Step::AlterColumns, statement)
+    }
+
+    /// A constraint, as written in a table definition.
+    fn constraint(&self, constraint: &Constraint) -> String {
+        match constraint.name {
+            Some(ref name) => format!("CONSTRAINT {} {}", self.name(name), constraint.definition),
+            None => constraint.definition.clone(),
+        }
+    }
+
+    fn add_constraint(&mut self, table: &str, constraint: &Constraint, change: &SchemaChange) {
+        if self.backend == Backend::Sqlite {
+            return self.manual(Step::AddConstraints, change);
+        }
+        let statement = format!(
+            "ALTER TABLE {} ADD {}",
+            self.name(table),
+            self.constraint(constraint)
+        );
+        self.push(Step::AddConstraints, statement)
+    }
+
+    fn drop_constraint(&mut self, table: &str, constraint: &Constraint, change: &SchemaChange) {
+        let definition = &constraint.definition;
+        let clause = match (self.backend, constraint.name.as_deref()) {
+            (Backend::MySql, _) if definition.starts_with("PRIMARY") => {
+                String::from("DROP PRIMARY KEY")
+            }
+            (Backend::MySql, Some(name)) if definition.starts_with("FOREIGN") => {
+                format!("DROP FOREIGN KEY {}", self.name(name))
+            }
+            (Backend::MySql, Some(name)) if definition.starts_with("CHECK") => {
+                format!("DROP CHECK {}", self.name(name))
+            }
+            (Backend::MySql | Backend::Postgres, Some(name)) => {
+                format!("DROP CONSTRAINT {}", self.name(name))
+            }
+            _ => return self.manual(Step::DropConstraints, change),
+        };
+        let statement = format!("ALTER TABLE {} {clause}", self.name(table));
+        self.push(Step::DropConstraints, statement)
+    }
+
+    fn create_index(&mut self, index: &Index) {
+        // `mysqldump` writes the kind of the index before `KEY`.
+        let (kind, definition) = ["FULLTEXT ", "SPATIAL "]
+            .into_iter()
+            .find_map(|kind| Some((kind, index.definition.strip_prefix(kind)?)))
+            .unwrap_or(("", &index.definition));
+        let statement = format!(
+            "CREATE {}{kind}INDEX {} ON {} {definition}",
+            if index.unique { "UNIQUE " } else { "" },
+            self.name(&index.name),
+            self.name(&index.table),
+        );
+        self.push(Step::CreateIndexes, statement)
+    }
+
+    fn drop_index(&mut self, index: &Index) {
+        let statement = match self.backend {
+            Backend::MySql => format!(
+                "DROP INDEX {} ON {}",
+                self.name(&index.name),
+                self.name(&index.table)
+            ),
+            // Indexes live in the schema of their table.
+            Backend::Postgres => match index.table.rsplit_once('.') {
+                Some((schema, _)) => format!(
+                    "DROP INDEX {}.{}",
+                    self.name(schema),
+                    self.name(&index.name)
+                ),
+                None => format!("DROP INDEX {}", self.name(&index.name)),
+            },
+            Backend::Sqlite => format!("DROP INDEX {}", self.name(&index.name)),
+        };
+        self.push(Step::DropIndexes, statement)
+    }
+
+    /// `MATERIALIZED ` for the materialized views of `pg_dump`, which are created empty.
+    fn materialized(&self, view: &View) -> &'static str {
+        match self.backend == Backend::Postgres
+            && (view.definition.ends_with("WITH NO DATA") || view.definition.ends_with("WITH DATA"))
+        {
+            true => "MATERIALIZED ",
+            false => "",
+        }
+    }
+
+    fn create_view(&mut self, view: &View) {
+        let statement = format!(
+            "CREATE {}VIEW {} AS {}",
+            self.materialized(view),
+            self.name(&view.name),
+            view.definition
+        );
+        self.push(Step::CreateViews, statement)
+    }
+
+    fn drop_view(&mut self, view: &View) {
+        let statement = format!(
+            "DROP {}VIEW {}",
+            self.materialized(view),
+            self.name(&view.name)
+        );
+        self.push(Step::DropViews, statement)
+    }
+}
+
+/// Whether `table` is the migrations table `migrations_table`, possibly qualified
+/// differently, which a database migrated by hand has but the migrations' schema doesn't.
+pub(crate) fn is_migrations_table(table: &str, migrations_table: &str) -> bool {
+    let unqualified = |name: &str| {
+        name.rsplit_once('.')
+            .map_or(name, |(_, name)| name)
+            .to_owned()
+    };
+    table == migrations_table || unqualified(table) == unqualified(migrations_table)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Backend;
+
+    #[test]
+    fn test_statements() -> Result<(), crate::error::Error> {
+        let current = crate::diff::Schema::parse(
+            "CREATE TABLE public.users (
+    id integer NOT NULL,
+    email text NOT NULL,
+    legacy text
+);
+ALTER TABLE ONLY public.users ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+CREATE INDEX users_email ON public.users USING btree (email);
+CREATE VIEW public.emails AS SELECT users.email FROM public.users;",
+            Backend::Postgres,
+        );
+        let target = crate::diff::Schema::parse(
+            "CREATE TABLE public.users (
+    id integer NOT NULL,
+    email character varying(320),
+    locale text DEFAULT 'en'::text NOT NULL
+);
+CREATE TABLE public.roles (
+    id integer NOT NULL,
+    user_id integer
+);
+ALTER TABLE ONLY public.roles ALTER COLUMN id SET DEFAULT nextval('public.roles_id_seq'::regclass);
+ALTER TABLE ONLY public.users ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+ALTER TABLE ONLY public.roles ADD CONSTRAINT roles_pkey PRIMARY KEY (id);
+ALTER TABLE ONLY public.roles
+    ADD CONSTRAINT roles_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.users(id);
+CREATE UNIQUE INDEX users_email ON public.users USING btree (email);
+CREATE VIEW public.emails AS SELECT users.email FROM public.users WHERE users.email IS NOT NULL;",
+            Backend::Postgres,
+        );
+
+        let (up, manual) = super::statements(&current.diff(&target), Backend::Postgres);
+        assert_eq!(
+            up,
+            r#"DROP VIEW "public"."emails";
+DROP INDEX "public"."users_email";
+ALTER TABLE "public"."users" DROP COLUMN "legacy";
+CREATE SEQUENCE IF NOT EXISTS public.roles_id_seq;
+CREATE TABLE "public"."roles" (
+    "id" integer NOT NULL DEFAULT nextval('public.roles_id_seq'::regclass),
+    "user_id" integer,
+    CONSTRAINT "roles_pkey" PRIMARY KEY(id)
+);
+ALTER TABLE "public"."users" ALTER COLUMN "email" TYPE character varying(320), ALTER COLUMN "email" DROP NOT NULL;
+ALTER TABLE "public"."users" ADD COLUMN "locale" text NOT NULL DEFAULT 'en'::text;
+ALTER SEQUENCE public.roles_id_seq OWNED BY "public"."roles"."id";
+ALTER TABLE "public"."roles" ADD CONSTRAINT "roles_user_id_fkey" FOREIGN KEY(user_id) REFERENCES public.users(id);
+CREATE UNIQUE INDEX "users_email" ON "public"."users" USING btree(email);
+CREATE VIEW "public"."emails" AS SELECT users.email FROM public.users WHERE users.email IS NOT NULL;
+"#
+        );
+        assert!(manual.is_empty());
+
+        let (down, _) = super::statements(&target.diff(&current), Backend::Postgres);
+        assert_eq!(
+            down,
+            r#"DROP VIEW "public"."emails";
+DROP INDEX "public"."users_email";
+ALTER TABLE "public"."users" DROP COLUMN "locale";
+DROP TABLE "public"."roles";
+ALTER TABLE "public"."users" ALTER COLUMN "email" TYPE text, ALTER COLUMN "email" SET NOT NULL;
+ALTER TABLE "public"."users" ADD COLUMN "legacy" text;
+CREATE INDEX "users_email" ON "public"."users" USING btree(email);
+CREATE VIEW "public"."emails" AS SELECT users.email FROM public.users;
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_statements_mysql() -> Result<(), crate::error::Error> {
+        let current = crate::diff::Schema::parse(
+            "CREATE TABLE `posts` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `user_id` int NOT NULL,
+  `body` text,
+  PRIMARY KEY (`id`),
+  CONSTRAINT `posts_user_id` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`)
+) ENGINE=InnoDB;",
+            Backend::MySql,
+        );
+        let target = crate::diff::Schema::parse(
+            "CREATE TABLE `posts` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `user_id` int NOT NULL,
+  `body` mediumtext NOT NULL,
+  PRIMARY KEY (`id`),
+  FULLTEXT KEY `posts_body` (`body`)
+) ENGINE=InnoDB;",
+            Backend::MySql,
+        );
+        let (up, _) = super::statements(&current.diff(&target), Backend::MySql);
+        assert_eq!(
+            up,
+            "ALTER TABLE `posts` DROP FOREIGN KEY `posts_user_id`;
+ALTER TABLE `posts` MODIFY COLUMN `body` mediumtext NOT NULL;
+CREATE FULLTEXT INDEX `posts_body` ON `posts` (`body`);
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_statements_sqlite() -> Result<(), crate::error::Error> {
+        let current = crate::diff::Schema::parse(
+            "CREATE TABLE users (id TEXT PRIMARY KEY NOT NULL, email TEXT NOT NULL);",
+            Backend::Sqlite,
+        );
+        let target = crate::diff::Schema::parse(
+            "CREATE TABLE users (id TEXT PRIMARY KEY NOT NULL, email TEXT, name TEXT);",
+            Backend::Sqlite,
+        );
+        let diff = current.diff(&target);
+        let (up, manual) = super::statements(&diff, Backend::Sqlite);
+        assert_eq!(
+            up,
+            r#"-- TODO (sqlite): changed column users.email: NOT NULL -> NULL
+ALTER TABLE "users" ADD COLUMN "name" TEXT;
+"#
+        );
+        assert_eq!(manual, diff.changes()[..1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_migrations_table() {
+        assert!(super::is_migrations_table(
+            "_sqlx_migrations",
+            "_sqlx_migrations"
+        ));
+        assert!(super::is_migrations_table(
+            "public._sqlx_migrations",
+            "_sqlx_migrations"
+        ));
+        assert!(super::is_migrations_table("migrations", "app.migrations"));
+        assert!(!super::is_migrations_table(
+            "public.users",
+            "_sqlx_migrations"
+        ));
+    }
+}
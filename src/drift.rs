@@ -0,0 +1,391 @@
+//! Comparing dumps statement by statement, for drift checks that only fail on real schema
+//! differences.
+//!
+//! Both dumps are split into statements, which are canonicalized: comments (such as the
+//! comment blocks of `pg_dump` and the provenance header) are dropped, whitespace and
+//! identifier quoting are normalized, and the default character set, collation and
+//! `AUTO_INCREMENT` counter that `mysqldump` writes after every table are left out.
+//! Session settings (`SET`, `LOCK TABLES`...) are ignored. The statements are then grouped
+//! by the object they define, such as `table public.users`, and compared per object
+//! regardless of their order.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    diff::{parenthesized, qualified_name, render, skip, strip, tokens, top_level, Token},
+    Backend,
+};
+
+/// Result of [`DatabaseSchema::compare`](crate::DatabaseSchema::compare) and
+/// [`DatabaseSchema::check_drift`](crate::DatabaseSchema::check_drift).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DriftReport {
+    pub(crate) objects: Vec<DriftedObject>,
+}
+
+impl DriftReport {
+    /// Whether both dumps define the same objects the same way.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The objects whose statements differ, by name.
+    pub fn objects(&self) -> &[DriftedObject] {
+        &self.objects
+    }
+}
+
+impl std::fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for object in &self.objects {
+            write!(f, "{object}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An object defined differently by the two dumps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriftedObject {
+    pub(crate) object: String,
+    pub(crate) missing: Vec<String>,
+    pub(crate) unexpected: Vec<String>,
+}
+
+impl DriftedObject {
+    /// Kind and name of the object, such as `table public.users` or `index users_email`.
+    pub fn object(&self) -> &str {
+        &self.object
+    }
+
+    /// Canonical statements of the expected dump that the actual one doesn't have.
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Canonical statements of the actual dump that the expected one doesn't have.
+    pub fn unexpected(&self) -> &[String] {
+        &self.unexpected
+    }
+}
+
+impl std::fmt::Display for DriftedObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}:", self.object)?;
+        for statement in &self.missing {
+            writeln!(f, "  - {statement}")?;
+        }
+        for statement in &self.unexpected {
+            writeln!(f, "  + {statement}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the dumps `expected` and `actual` of `backend`.
+pub(crate) fn compare(expected: &str, actual: &str, backend: Backend) -> DriftReport {
+    let expected = canonicalize(expected, backend);
+    let mut actual = canonicalize(actual, backend);
+    let mut objects = Vec::new();
+    for (object, expected) in expected {
+        let actual = actual.remove(&object).unwrap_or_default();
+        if expected != actual {
+            objects.push(DriftedObject {
+                object,
+                missing: difference(&expected, &actual),
+                unexpected: difference(&actual, &expected),
+            });
+        }
+    }
+    for (object, unexpected) in actual {
+        objects.push(DriftedObject {
+            object,
+            missing: Vec::new(),
+            unexpected,
+        });
+    }
+    objects.sort_by(|a, b| a.object.cmp(&b.object));
+    DriftReport { objects }
+}
+
+/// The sorted statements of `from` that aren't in the sorted `to`, counting duplicates.
+fn difference(from: &[String], to: &[String]) -> Vec<String> {
+    let mut to = to.iter().peekable();
+    let mut difference = Vec::new();
+    for statement in from {
+        while to.next_if(|other| *other < statement).is_some() {}
+        if to.next_if(|other| *other == statement).is_none() {
+            difference.push(statement.clone());
+        }
+    }
+    difference
+}
+
+/// The canonical statements of `sql`, sorted and grouped by object.
+fn canonicalize(sql: &str, backend: Backend) -> BTreeMap<String, Vec<String>> {
+    let mut objects = BTreeMap::<String, Vec<String>>::new();
+    for statement in crate::sql::split(sql, backend) {
+        let tokens = tokens(&statement.tokens, backend);
+        if tokens.is_empty() || is_session(&tokens) {
+            continue;
+        }
+        let tokens = match backend {
+            Backend::MySql => without_table_defaults(tokens),
+            _ => tokens,
+        };
+        objects
+            .entry(object(&tokens))
+            .or_default()
+            .push(render(&tokens, backend));
+    }
+    for statements in objects.values_mut() {
+        statements.sort();
+    }
+    objects
+}
+
+/// Whether the statement only sets up the session of the dump or of its restore.
+fn is_session(tokens: &[Token]) -> bool {
+    match tokens {
+        [first, ..]
+            if ["SET", "LOCK", "UNLOCK", "USE"]
+                .iter()
+                .any(|keyword| first.is(keyword)) =>
+        {
+            true
+        }
+        [select, schema, dot, function, ..] if select.is("SELECT") => {
+            schema.is("pg_catalog") && dot.is_symbol(".") && function.is("set_config")
+        }
+        // `mysqldump` around the rows of a table
+        [alter, .., toggle, keys] if alter.is("ALTER") => {
+            (toggle.is("DISABLE") || toggle.is("ENABLE")) && keys.is("KEYS")
+        }
+        _ => false,
+    }
+}
+
+/// `tokens` without the table options of `mysqldump` that depend on the server defaults
+/// and on the rows: `DEFAULT CHARSET`, `COLLATE` and `AUTO_INCREMENT`.
+fn without_table_defaults(tokens: Vec<Token>) -> Vec<Token> {
+    if strip(&tokens, &["CREATE", "TABLE"]).is_none() {
+        return tokens;
+    }
+    // The options follow the parentheses of the columns.
+    let Some(open) = tokens.iter().position(|token| token.is_symbol("(")) else {
+        return tokens;
+    };
+    let Some(options) = parenthesized(&tokens[open..]).map(|(_, options)| options) else {
+        return tokens;
+    };
+    let mut kept = tokens[..tokens.len() - options.len()].to_vec();
+    let mut rest = options;
+    while let Some(first) = rest.first() {
+        let option = skip(rest, &["DEFAULT"]);
+        let value = strip(option, &["CHARSET"])
+            .or_else(|| strip(option, &["CHARACTER", "SET"]))
+            .or_else(|| strip(option, &["COLLATE"]))
+            .or_else(|| strip(option, &["AUTO_INCREMENT"]));
+        match value.map(|value| skip_symbol(value, "=")) {
+            Some([_, after @ ..]) => rest = after,
+            _ => {
+                kept.push(first.clone());
+                rest = &rest[1..];
+            }
+        }
+    }
+    kept
+}
+
+/// `tokens` without the leading `symbol` if it starts with it.
+fn skip_symbol<'a>(tokens: &'a [Token], symbol: &str) -> &'a [Token] {
+    match tokens.first() {
+        Some(first) if first.is_symbol(symbol) => &tokens[1..],
+        _ => tokens,
+    }
+}
+
+/// Kinds of objects, as written after `CREATE` or `ALTER`.
+const KINDS: [&str; 18] = [
+    "TABLE",
+    "VIEW",
+    "INDEX",
+    "SEQUENCE",
+    "FUNCTION",
+    "PROCEDURE",
+    "TRIGGER",
+    "TYPE",
+    "DOMAIN",
+    "SCHEMA",
+    "EXTENSION",
+    "EVENT",
+    "POLICY",
+    "RULE",
+    "AGGREGATE",
+    "COLLATION",
+    "PUBLICATION",
+    "STATISTICS",
+];
+
+/// The object a statement defines, such as `table public.users`.
+fn object(tokens: &[Token]) -> String {
+    let named = |kind: &Token, rest: &[Token]| -> Option<String> {
+        let rest = skip(rest, &["CONCURRENTLY"]);
+        let rest = skip(skip(rest, &["ONLY"]), &["IF", "NOT", "EXISTS"]);
+        let (name, _) = qualified_name(skip(rest, &["IF", "EXISTS"]))?;
+        Some(format!("{} {name}", kind.identifier()?.to_lowercase()))
+    };
+
+    let rest = strip(tokens, &["CREATE"])
+        .or_else(|| strip(tokens, &["ALTER"]))
+        .or_else(|| strip(tokens, &["DROP"]));
+    if let Some(rest) = rest {
+        // The kind comes after modifiers such as `OR REPLACE`, `UNIQUE`, `MATERIALIZED`,
+        // or the algorithm and definer of `mysqldump` views.
+        let kind = top_level(rest)
+            .take_while(|(_, token)| !token.is("AS") && !token.is_symbol("("))
+            .find(|(_, token)| KINDS.iter().any(|kind| token.is(kind)));
+        if let Some((index, kind)) = kind {
+            if let Some(object) = named(kind, &rest[index + 1..]) {
+                return object;
+            }
+        }
+    } else if let Some(rest) = strip(tokens, &["COMMENT", "ON"]) {
+        if let Some(kind) = rest.first() {
+            // The comment of a column goes with its table.
+            if kind.is("COLUMN") {
+                if let Some((name, _)) = qualified_name(&rest[1..]) {
+                    if let Some((table, _)) = name.rsplit_once('.') {
+                        return format!("table {table}");
+                    }
+                }
+            } else if let Some(object) = named(kind, &rest[1..]) {
+                return object;
+            }
+        }
+    } else if let Some(rest) = strip(tokens, &["INSERT", "INTO"]) {
+        if let Some((table, _)) = qualified_name(rest) {
+            return format!("rows of {table}");
+        }
+    }
+    String::from("other statements")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Backend;
+
+    #[test]
+    fn test_compare_postgres() {
+        let expected = "--
+-- PostgreSQL database dump
+--
+
+SET statement_timeout = 0;
+SELECT pg_catalog.set_config('search_path', '', false);
+
+--
+-- Name: users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    email text NOT NULL
+);
+
+CREATE INDEX users_email ON public.users USING btree (email);
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+
+";
+        let reordered = "SET transaction_timeout = 0;
+ALTER TABLE ONLY public.users ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+CREATE INDEX users_email ON public.users USING btree (email);
+CREATE TABLE public.users (id integer NOT NULL, email text NOT NULL);
+";
+        assert!(super::compare(expected, reordered, Backend::Postgres).is_empty());
+
+        let changed = "CREATE TABLE public.users (
+    id integer NOT NULL,
+    email character varying(320) NOT NULL
+);
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+CREATE VIEW public.emails AS SELECT email FROM public.users;
+";
+        let report = super::compare(expected, changed, Backend::Postgres);
+        assert_eq!(
+            report.to_string(),
+            "index users_email:
+  - CREATE INDEX users_email ON public.users USING btree(email)
+table public.users:
+  - CREATE TABLE public.users(id integer NOT NULL, email text NOT NULL)
+  + CREATE TABLE public.users(id integer NOT NULL, email character varying(320) NOT NULL)
+view public.emails:
+  + CREATE VIEW public.emails AS SELECT email FROM public.users
+"
+        );
+    }
+
+    #[test]
+    fn test_compare_mysql() {
+        let expected = "/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+DROP TABLE IF EXISTS `users`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+CREATE TABLE `users` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `email` varchar(255) COLLATE utf8mb4_bin NOT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB AUTO_INCREMENT=42 DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;
+/*!40101 SET character_set_client = @saved_cs_client */;
+LOCK TABLES `users` WRITE;
+/*!40000 ALTER TABLE `users` DISABLE KEYS */;
+INSERT INTO `users` VALUES (1,'a@example.com');
+/*!40000 ALTER TABLE `users` ENABLE KEYS */;
+UNLOCK TABLES;
+-- Dump completed on 2024-01-01  0:00:00
+";
+        let actual = "DROP TABLE IF EXISTS `users`;
+CREATE TABLE `users` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `email` varchar(255) COLLATE utf8mb4_bin NOT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci;
+INSERT INTO `users` VALUES (1,'a@example.com');
+";
+        assert!(super::compare(expected, actual, Backend::MySql).is_empty());
+
+        let report = super::compare(
+            expected,
+            &actual.replace("COLLATE utf8mb4_bin", "COLLATE utf8mb4_general_ci"),
+            Backend::MySql,
+        );
+        assert_eq!(
+            report
+                .objects()
+                .iter()
+                .map(|object| object.object())
+                .collect::<Vec<&str>>(),
+            ["table users"]
+        );
+    }
+
+    #[test]
+    fn test_difference() {
+        let statements = |statements: &[&str]| -> Vec<String> {
+            statements
+                .iter()
+                .map(|statement| statement.to_string())
+                .collect()
+        };
+        assert_eq!(
+            super::difference(
+                &statements(&["a", "a", "b", "d"]),
+                &statements(&["a", "c", "d"])
+            ),
+            statements(&["a", "b"])
+        );
+    }
+}
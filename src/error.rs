@@ -7,6 +7,52 @@ pub enum Error {
     #[error("IO error: {0}")]
     /// Any kind of IO error
     IOError(#[from] std::io::Error),
+    #[error("Environment variable {0} is invalid: {1}")]
+    /// An environment variable used for configuration is set but invalid
+    EnvironmentVariable(&'static str, std::env::VarError),
+    #[cfg(feature = "dotenv")]
+    #[error("Unable to load .env file: {0}")]
+    /// The `.env` file exists but couldn't be loaded
+    DotenvError(#[from] dotenvy::Error),
+    #[cfg(feature = "config")]
+    #[error("Invalid project configuration: {0}")]
+    /// The project configuration file couldn't be read or is invalid
+    ConfigError(String),
+    #[error("Unsupported backend `{0}`, make sure its feature is enabled")]
+    /// The backend isn't known or its feature isn't enabled
+    UnsupportedBackend(String),
+    #[error("Unable to read the migrations: {0}")]
+    /// The migrations can't be read from the migration source
+    MigrationSourceError(String),
+    #[error("No migration has the target version {0}")]
+    /// The version to migrate up to isn't the one of any migration
+    UnknownTargetVersion(String),
+    #[error("`{0}` isn't the name of an existing table to dump the data of")]
+    /// A table listed for its data is invalid or doesn't exist
+    InvalidDataTable(String),
+    #[error("Migration {0} failed: {1}")]
+    /// A plain SQL migration failed to apply
+    PlainMigrationFailed(String, String),
+    #[error("The database applied some of the migrations squashed into the baseline {0}, but not all of them")]
+    /// The database is in between migrations squashed into a baseline
+    PartiallyMigrated(String),
+    #[error("Unknown lint rule `{0}`")]
+    /// The name of a lint rule, in a comment marker or elsewhere, isn't known
+    UnknownLintRule(String),
+    #[error(
+        "{} unsafe statement(s) in the migrations:\n{}",
+        .0.len(),
+        .0.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n")
+    )]
+    /// The linter found dangerous statements in the migrations
+    UnsafeMigrations(Vec<crate::lint::LintWarning>),
+    #[error(
+        "{} statement(s) failed to load:\n{}",
+        .0.len(),
+        .0.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n")
+    )]
+    /// Statements of the loaded dump failed to run
+    LoadFailed(Vec<crate::load::StatementError>),
     #[error("Command run error: {0}")]
     /// Any kind of error when running a command
     CommandRunError(String),
@@ -26,6 +72,14 @@ pub enum Error {
     #[error("DB error: {0}")]
     /// Any connection error when running migrations in `diesel`
     MigrationError(#[from] diesel_migrations::MigrationError),
+    #[cfg(feature = "diesel")]
+    #[error("Migration error: {0}")]
+    /// Any error returned by the `diesel` migration harness
+    MigrationHarnessError(String),
+    #[cfg(any(feature = "mysql", feature = "postgres"))]
+    #[error("Unable to connect to the database after {} attempts:\n{}", .0.len(), .0.join("\n"))]
+    /// Connecting to the database kept failing, with the error of every attempt
+    ConnectionRetriesExhausted(Vec<String>),
     #[cfg(any(feature = "mysql", feature = "postgres"))]
     #[error("Unable to extract database name from connection string")]
     /// Extracting the database name from the connection string failed
@@ -38,4 +92,19 @@ pub enum Error {
     #[error("Uri configuration encoding error: {0}")]
     /// Error when decoding parts of the connection string
     UriConfigurationDecoding(#[from] core::str::Utf8Error),
+    #[cfg(any(feature = "mysql", feature = "postgres"))]
+    #[error("Unable to find `{0}`, add it to PATH or set its location in the builder or the environment")]
+    /// The external dump tool could not be found
+    DumpToolNotFound(&'static str),
+    #[cfg(any(feature = "mysql", feature = "postgres"))]
+    #[error("`{tool}` version {client} cannot dump server version {server}, install a more recent client")]
+    /// None of the dump tools found is recent enough for the server
+    DumpToolVersionMismatch {
+        /// Name of the dump tool
+        tool: &'static str,
+        /// Most recent version of the dump tool that was found
+        client: String,
+        /// Version of the server
+        server: String,
+    },
 }
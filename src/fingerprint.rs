@@ -0,0 +1,108 @@
+//! Skipping the dump when nothing that goes into it changed.
+//!
+//! After a dump, a sidecar file (`structure.sql.fingerprint` next to `structure.sql`)
+//! records a hash of the inputs (the contents of the migrations, the dump options and the
+//! crate version) and a hash of the dump itself. The next dump is skipped, without even
+//! connecting to the database, if both still match: the inputs are the same and the dump
+//! wasn't edited or deleted in between.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// Path of the sidecar file of `destination_path`.
+fn sidecar_path(destination_path: &Path) -> PathBuf {
+    let mut path = destination_path.as_os_str().to_owned();
+    path.push(".fingerprint");
+    PathBuf::from(path)
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    crate::migrations::hex(&Sha256::digest(bytes))
+}
+
+/// Hash everything that goes into the dump.
+///
+/// `options` are the dump options, one `name=value` part each. Embedded migrations only
+/// show their versions there, hence the separate checksum of the migrations.
+pub(crate) fn inputs(
+    options: &[String],
+    migrations: &crate::migrations::MigrationSource,
+) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    let checksum = migrations.checksum()?;
+    let parts = [env!("CARGO_PKG_VERSION"), crate::FRAMEWORK, &checksum];
+    for part in parts.into_iter().chain(options.iter().map(String::as_str)) {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    Ok(crate::migrations::hex(&hasher.finalize()))
+}
+
+/// Whether `destination_path` was dumped from the same `inputs` and left untouched since.
+pub(crate) fn is_fresh(destination_path: &Path, inputs: &str) -> Result<bool, Error> {
+    let sidecar = match std::fs::read_to_string(sidecar_path(destination_path)) {
+        Ok(sidecar) => sidecar,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error.into()),
+    };
+    let output = match std::fs::read(destination_path) {
+        Ok(output) => output,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error.into()),
+    };
+    let field = |name: &str| {
+        sidecar.lines().find_map(|line| {
+            line.strip_prefix(name)
+                .and_then(|line| line.strip_prefix(" sha256:"))
+        })
+    };
+    Ok(field("inputs") == Some(inputs) && field("output") == Some(&sha256(&output)))
+}
+
+/// Record that `destination_path` was just dumped from `inputs`.
+pub(crate) fn write(destination_path: &Path, inputs: &str) -> Result<(), Error> {
+    let output = std::fs::read(destination_path)?;
+    Ok(std::fs::write(
+        sidecar_path(destination_path),
+        format!(
+            "# Written by database-schema to skip dumping when nothing changed.\ninputs sha256:{inputs}\noutput sha256:{}\n",
+            sha256(&output)
+        ),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            super::sidecar_path(Path::new("db/structure.sql")),
+            Path::new("db/structure.sql.fingerprint")
+        );
+    }
+
+    #[test]
+    fn test_is_fresh() -> Result<(), crate::error::Error> {
+        let destination_path =
+            std::env::temp_dir().join(format!("{}-fingerprint-structure.sql", crate::FRAMEWORK));
+        let _ = std::fs::remove_file(super::sidecar_path(&destination_path));
+        std::fs::write(&destination_path, "CREATE TABLE users (id TEXT);\n")?;
+        assert!(!super::is_fresh(&destination_path, "abc")?);
+
+        super::write(&destination_path, "abc")?;
+        assert!(super::is_fresh(&destination_path, "abc")?);
+        assert!(!super::is_fresh(&destination_path, "def")?);
+
+        std::fs::write(&destination_path, "CREATE TABLE posts (id TEXT);\n")?;
+        assert!(!super::is_fresh(&destination_path, "abc")?);
+
+        std::fs::remove_file(&destination_path)?;
+        assert!(!super::is_fresh(&destination_path, "abc")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Provenance header written at the top of every dump.
+//!
+//! The header is a block of SQL comments, so the dump can still be loaded as is:
+//!
+//! ```sql
+//! --
+//! -- Generated by database-schema 0.2.0
+//! -- Backend: postgres (sqlx)
+//! -- Latest migration: 20230827160610
+//! -- Migrations checksum: sha256:5c0d...
+//! -- Generated at: 2023-08-27T16:06:10Z
+//! --
+//! ```
+
+/// Controls which lines end up in the header written at the top of the dump.
+///
+/// Every part can be switched off individually. The timestamp is off by default so
+/// that dumping the same migrations twice produces the same file.
+///
+/// ```rust,ignore
+/// DatabaseSchemaBuilder::new()
+///     .header(HeaderOptions::new().generated_at(true))
+///     .build()
+///     .dump()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderOptions {
+    pub(crate) crate_version: bool,
+    pub(crate) backend: bool,
+    pub(crate) latest_migration: bool,
+    pub(crate) migrations_checksum: bool,
+    pub(crate) generated_at: bool,
+}
+
+impl Default for HeaderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeaderOptions {
+    /// Creates the default set of options: everything but the generation timestamp.
+    pub fn new() -> Self {
+        Self {
+            crate_version: true,
+            backend: true,
+            latest_migration: true,
+            migrations_checksum: true,
+            generated_at: false,
+        }
+    }
+
+    /// Whether to record the version of `database-schema` that generated the dump.
+    pub fn crate_version(mut self, enabled: bool) -> Self {
+        self.crate_version = enabled;
+        self
+    }
+
+    /// Whether to record the database backend and the migration framework (`sqlx` or
+    /// `diesel`).
+    pub fn backend(mut self, enabled: bool) -> Self {
+        self.backend = enabled;
+        self
+    }
+
+    /// Whether to record the version of the latest migration applied before dumping.
+    pub fn latest_migration(mut self, enabled: bool) -> Self {
+        self.latest_migration = enabled;
+        self
+    }
+
+    /// Whether to record a SHA-256 checksum of the migrations directory.
+    ///
+    /// Tooling can compare it against the migrations on disk to decide whether the dump
+    /// needs to be regenerated. For embedded migrations, the checksum is computed from
+    /// the migrations themselves (only their names with `diesel`), so it differs from the
+    /// one of the directory they were embedded from.
+    pub fn migrations_checksum(mut self, enabled: bool) -> Self {
+        self.migrations_checksum = enabled;
+        self
+    }
+
+    /// Whether to record when the dump was generated, in UTC.
+    ///
+    /// Disabled by default because it makes every dump differ from the previous one.
+    pub fn generated_at(mut self, enabled: bool) -> Self {
+        self.generated_at = enabled;
+        self
+    }
+}
+
+/// Render the header for the dump of `migrations` against `connection_url`, migrated up
+/// to the `target` version if set.
+pub(crate) fn render(
+    options: &HeaderOptions,
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
+) -> Result<String, crate::error::Error> {
+    let mut lines = Vec::new();
+    if options.crate_version {
+        lines.push(format!(
+            "Generated by database-schema {}",
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+    if options.backend {
+        lines.push(format!(
+            "Backend: {} ({})",
+            crate::Backend::from_connection_url(connection_url),
+            crate::FRAMEWORK
+        ));
+    }
+    if options.latest_migration {
+        let versions = migrations.versions()?;
+        let latest = versions.iter().rfind(|version| {
+            target.is_none_or(|target| {
+                crate::migrations::version_order(version)
+                    <= crate::migrations::version_order(target)
+            })
+        });
+        lines.push(format!(
+            "Latest migration: {}",
+            latest.map_or("none", String::as_str)
+        ));
+    }
+    if options.migrations_checksum {
+        lines.push(format!(
+            "Migrations checksum: sha256:{}",
+            migrations.checksum()?
+        ));
+    }
+    if options.generated_at {
+        lines.push(format!(
+            "Generated at: {}",
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ));
+    }
+
+    if lines.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!(
+        "--\n{}\n--\n\n",
+        lines
+            .iter()
+            .map(|line| format!("-- {line}"))
+            .collect::<Vec<String>>()
+            .join("\n")
+    ))
+}
+
+/// Prepend the header to the dump already written to `destination_path`.
+pub(crate) fn prepend<P: AsRef<std::path::Path>>(
+    header: &str,
+    destination_path: P,
+) -> Result<(), crate::error::Error> {
+    if header.is_empty() {
+        return Ok(());
+    }
+    let contents = std::fs::read_to_string(&destination_path)?;
+    Ok(std::fs::write(
+        destination_path,
+        format!("{header}{contents}"),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeaderOptions;
+    use crate::migrations::MigrationSource;
+
+    #[cfg(any(feature = "sqlx", feature = "cli"))]
+    const MIGRATIONS_PATH: &str = "./fixtures/sqlx/sqlite/migrations";
+    #[cfg(feature = "diesel")]
+    const MIGRATIONS_PATH: &str = "./fixtures/diesel/sqlite/migrations";
+
+    fn migrations() -> MigrationSource {
+        MigrationSource::Directory(MIGRATIONS_PATH.into())
+    }
+
+    #[test]
+    fn test_render_default() -> Result<(), crate::error::Error> {
+        let header = super::render(
+            &HeaderOptions::new(),
+            "sqlite::memory:",
+            &migrations(),
+            None,
+        )?;
+        assert!(header.starts_with(&format!(
+            "--\n-- Generated by database-schema {}\n-- Backend: sqlite ({})\n-- Latest migration: 2023082",
+            env!("CARGO_PKG_VERSION"),
+            crate::FRAMEWORK,
+        )));
+        assert!(header.contains("\n-- Migrations checksum: sha256:"));
+        assert!(!header.contains("Generated at"));
+        assert!(header.ends_with("\n--\n\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_target_version() -> Result<(), crate::error::Error> {
+        let options = HeaderOptions::new()
+            .crate_version(false)
+            .backend(false)
+            .migrations_checksum(false);
+        assert_eq!(
+            super::render(&options, ":memory:", &migrations(), Some("20000101000000"))?,
+            "--\n-- Latest migration: none\n--\n\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_everything_disabled() -> Result<(), crate::error::Error> {
+        let options = HeaderOptions::new()
+            .crate_version(false)
+            .backend(false)
+            .latest_migration(false)
+            .migrations_checksum(false);
+        assert_eq!(
+            super::render(&options, ":memory:", &migrations(), None)?,
+            ""
+        );
+        Ok(())
+    }
+}
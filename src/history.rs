@@ -0,0 +1,134 @@
+//! Schema snapshots after every migration.
+//!
+//! With a history directory set on the builder, the pending migrations are applied one at
+//! a time instead of all at once, and the schema is dumped after each of them to
+//! `<history_dir>/<version>.sql`. Diffing two consecutive files shows exactly what a
+//! migration changed.
+
+use std::path::Path;
+
+use crate::{
+    error::Error,
+    migrations::{version_order, Snapshot},
+};
+
+/// Write the snapshot taken after the migration `version`.
+async fn write<C, S: Snapshot<C>>(
+    conn: &mut C,
+    snapshot: &S,
+    history_dir: &Path,
+    version: &str,
+) -> Result<(), Error> {
+    let path = history_dir.join(format!("{version}.sql"));
+    tracing::debug!(version, path = %path.display(), "Writing schema snapshot");
+    Ok(std::fs::write(path, snapshot.snapshot(conn).await?)?)
+}
+
+/// Whether the migration `version` is to be applied when migrating up to `target`.
+fn is_before(version: &str, target: Option<&str>) -> bool {
+    target.is_none_or(|target| version_order(version) <= version_order(target))
+}
+
+/// Apply the pending plain migrations on `conn`, writing a snapshot after each of them.
+async fn migrate_plain<C, S>(
+    conn: &mut C,
+    migrations: &crate::plain::PlainMigrations,
+    snapshot: &S,
+    history_dir: &Path,
+    target: Option<&str>,
+) -> Result<(), Error>
+where
+    C: crate::plain::Execute,
+    S: Snapshot<C>,
+{
+    for migration in crate::plain::pending(conn, migrations)
+        .await?
+        .into_iter()
+        .filter(|migration| is_before(&migration.version, target))
+    {
+        crate::plain::apply(conn, migrations, &migration).await?;
+        write(conn, snapshot, history_dir, &migration.version).await?;
+    }
+    Ok(())
+}
+
+/// Apply the pending migrations one at a time on `conn`, writing a snapshot of the schema
+/// to `history_dir` after each of them, only up to the `target` version (included) if set.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn migrate<C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+    history_dir: &Path,
+    target: Option<&str>,
+) -> Result<(), Error>
+where
+    C: sqlx::migrate::Migrate + crate::plain::Execute,
+    S: Snapshot<C>,
+{
+    std::fs::create_dir_all(history_dir)?;
+    if let crate::migrations::MigrationSource::Plain(plain) = migrations {
+        return migrate_plain(conn, plain, snapshot, history_dir, target).await;
+    }
+    let migrations = migrations.sqlx_migrations().await?;
+    for migration in crate::migrations::pending_sqlx_migrations(conn, &migrations, target).await? {
+        let _ = conn.apply(migration).await.map_err(sqlx::Error::from)?;
+        write(conn, snapshot, history_dir, &migration.version.to_string()).await?;
+    }
+    conn.unlock().await.map_err(sqlx::Error::from)?;
+    Ok(())
+}
+
+/// Apply the pending migrations one at a time on `conn`, writing a snapshot of the schema
+/// to `history_dir` after each of them, only up to the `target` version (included) if set.
+#[cfg(feature = "diesel")]
+pub(crate) async fn migrate<DB, C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+    history_dir: &Path,
+    target: Option<&str>,
+) -> Result<(), Error>
+where
+    DB: diesel::backend::Backend,
+    C: diesel_migrations::MigrationHarness<DB> + crate::plain::Execute,
+    S: Snapshot<C>,
+{
+    std::fs::create_dir_all(history_dir)?;
+    if let crate::migrations::MigrationSource::Plain(plain) = migrations {
+        return migrate_plain(conn, plain, snapshot, history_dir, target).await;
+    }
+    let harness_error = |error: Box<dyn std::error::Error + Send + Sync>| {
+        Error::MigrationHarnessError(error.to_string())
+    };
+    let pending = conn
+        .pending_migrations(migrations.diesel_migrations()?)
+        .map_err(harness_error)?;
+
+    for migration in pending
+        .iter()
+        .filter(|migration| is_before(&migration.name().version().to_string(), target))
+    {
+        let version = conn.run_migration(&**migration).map_err(harness_error)?;
+        write(conn, snapshot, history_dir, &version.to_string()).await?;
+    }
+    Ok(())
+}
+
+/// Apply the pending migrations one at a time on `conn`, writing a snapshot of the schema
+/// to `history_dir` after each of them, only up to the `target` version (included) if set.
+#[cfg(feature = "cli")]
+pub(crate) async fn migrate<C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+    history_dir: &Path,
+    target: Option<&str>,
+) -> Result<(), Error>
+where
+    C: crate::plain::Execute,
+    S: Snapshot<C>,
+{
+    std::fs::create_dir_all(history_dir)?;
+    migrate_plain(conn, migrations.plain()?, snapshot, history_dir, target).await
+}
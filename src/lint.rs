@@ -0,0 +1,581 @@
+//! Flagging operationally dangerous statements in migrations, before they are applied.
+//!
+//! The up migrations are split into statements, by the same splitter that loads dumps,
+//! and checked against a set of [`LintRule`]s, some of them specific to a backend.
+//! Statements on a table created by the same migration are never flagged, since nothing
+//! can be using that table yet.
+//!
+//! A migration can allow rules with a comment marker, for example when a table is known
+//! to be small:
+//!
+//! ```sql
+//! -- database-schema: allow(drop_column, column_type_change)
+//! ALTER TABLE users DROP COLUMN legacy_id;
+//! ```
+
+use crate::{error::Error, migrations::version_order, sql::TokenKind, Backend};
+
+/// Prefix of the comment marker allowing rules in a migration.
+const ALLOW_MARKER: &str = "database-schema:";
+
+/// A kind of operationally dangerous statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LintRule {
+    /// `ADD COLUMN ... NOT NULL` without a `DEFAULT`, which fails on tables with rows.
+    AddColumnNotNull,
+
+    /// `CREATE INDEX` without `CONCURRENTLY` on PostgreSQL, which blocks writes to the
+    /// table while the index is built.
+    CreateIndexNonConcurrently,
+
+    /// Changing the type of a column (`ALTER COLUMN ... TYPE`, or `MODIFY`/`CHANGE` on
+    /// MySQL), which usually rewrites the table under an exclusive lock.
+    ColumnTypeChange,
+
+    /// `DROP COLUMN`, which breaks the application still reading the column.
+    DropColumn,
+
+    /// `DROP TABLE`, which breaks the application still reading the table.
+    DropTable,
+
+    /// MySQL `ALTER TABLE`s that copy the table or block writes: `ALGORITHM=COPY`,
+    /// `LOCK=SHARED|EXCLUSIVE`, primary key changes, `CONVERT TO CHARACTER SET` and
+    /// `FULLTEXT`/`SPATIAL` indexes.
+    MySqlTableLock,
+}
+
+impl LintRule {
+    /// Every rule.
+    pub const ALL: [LintRule; 6] = [
+        LintRule::AddColumnNotNull,
+        LintRule::CreateIndexNonConcurrently,
+        LintRule::ColumnTypeChange,
+        LintRule::DropColumn,
+        LintRule::DropTable,
+        LintRule::MySqlTableLock,
+    ];
+
+    /// Name of the rule in comment markers, such as `drop_column`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::AddColumnNotNull => "add_column_not_null",
+            LintRule::CreateIndexNonConcurrently => "create_index_non_concurrently",
+            LintRule::ColumnTypeChange => "column_type_change",
+            LintRule::DropColumn => "drop_column",
+            LintRule::DropTable => "drop_table",
+            LintRule::MySqlTableLock => "mysql_table_lock",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            LintRule::AddColumnNotNull => "NOT NULL column added without a default",
+            LintRule::CreateIndexNonConcurrently => "index created without CONCURRENTLY",
+            LintRule::ColumnTypeChange => "column type changed",
+            LintRule::DropColumn => "column dropped",
+            LintRule::DropTable => "table dropped",
+            LintRule::MySqlTableLock => "ALTER TABLE copies or locks the table",
+        }
+    }
+}
+
+impl std::fmt::Display for LintRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for LintRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        LintRule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| Error::UnknownLintRule(s.to_owned()))
+    }
+}
+
+/// Which migrations and rules to lint.
+///
+/// ```rust,ignore
+/// DatabaseSchemaBuilder::new()
+///     .lint(
+///         LintOptions::new()
+///             .after("20240101000000")
+///             .allow(LintRule::DropTable),
+///     )
+///     .build()
+///     .dump()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintOptions {
+    pub(crate) after: Option<String>,
+    pub(crate) allowed: Vec<LintRule>,
+}
+
+impl LintOptions {
+    /// Creates the default set of options: every rule, on every migration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only lint the migrations with a version greater than `version`.
+    ///
+    /// Migrations that are already deployed can't be annotated with comment markers
+    /// anymore (`sqlx` would reject their changed checksum), so this is the way to leave
+    /// them alone.
+    pub fn after<S: Into<String>>(mut self, version: S) -> Self {
+        self.after = Some(version.into());
+        self
+    }
+
+    /// Allow `rule` in every migration.
+    pub fn allow(mut self, rule: LintRule) -> Self {
+        self.allowed.push(rule);
+        self
+    }
+}
+
+/// A dangerous statement found in a migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    pub(crate) version: String,
+    pub(crate) migration: String,
+    pub(crate) line: usize,
+    pub(crate) rule: LintRule,
+    pub(crate) statement: String,
+}
+
+impl LintWarning {
+    /// Version of the migration.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// File or directory name of the migration (its description with embedded `sqlx`
+    /// migrations).
+    pub fn migration(&self) -> &str {
+        &self.migration
+    }
+
+    /// Line of the migration where the statement starts, from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The rule the statement breaks.
+    pub fn rule(&self) -> LintRule {
+        self.rule
+    }
+
+    /// The statement, without comments and with its whitespace collapsed.
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} line {}: {} ({}): {}",
+            self.migration,
+            self.line,
+            self.rule.description(),
+            self.rule,
+            self.statement
+        )
+    }
+}
+
+/// Lint the up migrations of `migrations` for `backend`.
+pub(crate) fn lint(
+    options: &LintOptions,
+    backend: Backend,
+    migrations: &crate::migrations::MigrationSource,
+) -> Result<Vec<LintWarning>, Error> {
+    let mut warnings = Vec::new();
+    for migration in migrations.up_migrations()? {
+        if options
+            .after
+            .as_ref()
+            .is_some_and(|after| version_order(&migration.version) <= version_order(after))
+        {
+            continue;
+        }
+        let (statements, mut allowed) = split(&migration.sql, backend)?;
+        allowed.extend_from_slice(&options.allowed);
+        warnings.extend(
+            check(&statements, backend)
+                .into_iter()
+                .filter(|(_, rule)| !allowed.contains(rule))
+                .map(|(statement, rule)| LintWarning {
+                    version: migration.version.clone(),
+                    migration: migration.name.clone(),
+                    line: statement.line,
+                    rule,
+                    statement: statement.text.clone(),
+                }),
+        );
+    }
+    Ok(warnings)
+}
+
+/// A statement of a migration.
+#[derive(Debug, Default)]
+struct Statement {
+    /// Line where the statement starts.
+    line: usize,
+    /// Text without comments, whitespace collapsed.
+    text: String,
+    /// Uppercased words, identifiers (unquoted) and punctuation. String literals are
+    /// replaced by `''`.
+    tokens: Vec<String>,
+}
+
+fn starts_with(tokens: &[String], words: &[&str]) -> bool {
+    tokens.len() >= words.len() && tokens.iter().zip(words).all(|(token, word)| token == word)
+}
+
+/// Split `sql` into statements, and collect the rules allowed by comment markers.
+fn split(sql: &str, backend: Backend) -> Result<(Vec<Statement>, Vec<LintRule>), Error> {
+    let mut statements = Vec::new();
+    let mut allowed = Vec::new();
+    for statement in crate::sql::split(sql, backend) {
+        let mut current = Statement {
+            line: statement.line,
+            ..Statement::default()
+        };
+        for token in &statement.tokens {
+            let normalized = match token.kind {
+                TokenKind::LineComment => {
+                    let marker = token.text.strip_prefix("--").map(str::trim);
+                    if let Some(rules) = marker.and_then(|marker| marker.strip_prefix(ALLOW_MARKER))
+                    {
+                        allowed.extend(parse_marker(rules)?);
+                    }
+                    continue;
+                }
+                TokenKind::Whitespace | TokenKind::BlockComment | TokenKind::ConditionalComment => {
+                    if !current.text.is_empty() && !current.text.ends_with(' ') {
+                        current.text.push(' ');
+                    }
+                    continue;
+                }
+                TokenKind::String => String::from("''"),
+                TokenKind::QuotedIdentifier => token
+                    .text
+                    .get(1..token.text.len() - 1)
+                    .unwrap_or_default()
+                    .to_uppercase(),
+                TokenKind::Word | TokenKind::Punctuation => token.text.to_uppercase(),
+            };
+            current.text.push_str(token.text);
+            current.tokens.push(normalized);
+        }
+        if !current.tokens.is_empty() {
+            current.text = current.text.trim_end().to_owned();
+            statements.push(current);
+        }
+    }
+    Ok((statements, allowed))
+}
+
+/// Parse the `allow(rule, ...)` part of a comment marker.
+fn parse_marker(marker: &str) -> Result<Vec<LintRule>, Error> {
+    let rules = marker
+        .trim()
+        .strip_prefix("allow(")
+        .and_then(|rules| rules.trim_end().strip_suffix(')'))
+        .ok_or_else(|| {
+            Error::MigrationSourceError(format!(
+                "invalid marker `{ALLOW_MARKER}{marker}`, expected `{ALLOW_MARKER} allow(rule, ...)`"
+            ))
+        })?;
+    rules.split(',').map(|rule| rule.trim().parse()).collect()
+}
+
+/// Check `statements` against the rules, returning the statements that break them.
+fn check(statements: &[Statement], backend: Backend) -> Vec<(&Statement, LintRule)> {
+    let mut created = Vec::new();
+    let mut broken = Vec::new();
+    for statement in statements {
+        let tokens = &statement.tokens;
+        if let Some(rest) = strip(tokens, &["CREATE"]) {
+            let rest = strip(rest, &["TEMPORARY"])
+                .or_else(|| strip(rest, &["TEMP"]))
+                .or_else(|| strip(rest, &["UNLOGGED"]))
+                .unwrap_or(rest);
+            if let Some(rest) = strip(rest, &["TABLE"]) {
+                let rest = strip(rest, &["IF", "NOT", "EXISTS"]).unwrap_or(rest);
+                created.push(table_name(rest).0);
+                continue;
+            }
+            let rest = strip(rest, &["UNIQUE"]).unwrap_or(rest);
+            if let Some(rest) = strip(rest, &["INDEX"]) {
+                if backend == Backend::Postgres && !starts_with(rest, &["CONCURRENTLY"]) {
+                    let on = rest.iter().position(|token| token == "ON");
+                    let table = on.map(|on| {
+                        let rest = &rest[on + 1..];
+                        table_name(strip(rest, &["ONLY"]).unwrap_or(rest)).0
+                    });
+                    if !table.is_some_and(|table| created.contains(&table)) {
+                        broken.push((statement, LintRule::CreateIndexNonConcurrently));
+                    }
+                }
+                continue;
+            }
+        }
+
+        if let Some(rest) = strip(tokens, &["DROP", "TABLE"]) {
+            let rest = strip(rest, &["IF", "EXISTS"]).unwrap_or(rest);
+            if !created.contains(&table_name(rest).0) {
+                broken.push((statement, LintRule::DropTable));
+            }
+            continue;
+        }
+
+        let Some(rest) = strip(tokens, &["ALTER", "TABLE"]) else {
+            continue;
+        };
+        let rest = strip(rest, &["IF", "EXISTS"]).unwrap_or(rest);
+        let rest = strip(rest, &["ONLY"]).unwrap_or(rest);
+        let (table, rest) = table_name(rest);
+        if created.contains(&table) {
+            continue;
+        }
+        let mut rules = Vec::new();
+        for clause in clauses(rest) {
+            rules.extend(check_clause(clause, backend));
+        }
+        rules.sort();
+        rules.dedup();
+        broken.extend(rules.into_iter().map(|rule| (statement, rule)));
+    }
+    broken
+}
+
+/// The rules broken by a clause of an `ALTER TABLE`.
+fn check_clause(clause: &[String], backend: Backend) -> Vec<LintRule> {
+    let mut rules = Vec::new();
+    let is_mysql = backend == Backend::MySql;
+    let contains = |words: &[&str]| clause.windows(words.len()).any(|w| starts_with(w, words));
+
+    if let Some(rest) = strip(clause, &["ADD"]) {
+        const NOT_COLUMNS: [&str; 11] = [
+            "CONSTRAINT",
+            "INDEX",
+            "KEY",
+            "PRIMARY",
+            "UNIQUE",
+            "FOREIGN",
+            "FULLTEXT",
+            "SPATIAL",
+            "CHECK",
+            "PARTITION",
+            "EXCLUDE",
+        ];
+        let is_column = starts_with(rest, &["COLUMN"])
+            || !rest
+                .first()
+                .is_some_and(|token| NOT_COLUMNS.contains(&token.as_str()));
+        if is_column && contains(&["NOT", "NULL"]) && !contains(&["DEFAULT"]) {
+            rules.push(LintRule::AddColumnNotNull);
+        }
+        if is_mysql
+            && (starts_with(rest, &["PRIMARY"])
+                || starts_with(rest, &["FULLTEXT"])
+                || starts_with(rest, &["SPATIAL"]))
+        {
+            rules.push(LintRule::MySqlTableLock);
+        }
+    } else if let Some(rest) = strip(clause, &["DROP"]) {
+        const NOT_COLUMNS: [&str; 9] = [
+            "CONSTRAINT",
+            "INDEX",
+            "KEY",
+            "PRIMARY",
+            "FOREIGN",
+            "CHECK",
+            "PARTITION",
+            "DEFAULT",
+            "TRIGGER",
+        ];
+        if starts_with(rest, &["COLUMN"])
+            || !rest
+                .first()
+                .is_some_and(|token| NOT_COLUMNS.contains(&token.as_str()))
+        {
+            rules.push(LintRule::DropColumn);
+        }
+        if is_mysql && starts_with(rest, &["PRIMARY"]) {
+            rules.push(LintRule::MySqlTableLock);
+        }
+    } else if (starts_with(clause, &["ALTER"]) && contains(&["TYPE"]))
+        || (is_mysql && (starts_with(clause, &["MODIFY"]) || starts_with(clause, &["CHANGE"])))
+    {
+        rules.push(LintRule::ColumnTypeChange);
+    }
+
+    if is_mysql
+        && (contains(&["ALGORITHM", "=", "COPY"])
+            || contains(&["LOCK", "=", "SHARED"])
+            || contains(&["LOCK", "=", "EXCLUSIVE"])
+            || contains(&["CONVERT", "TO"]))
+    {
+        rules.push(LintRule::MySqlTableLock);
+    }
+    rules
+}
+
+/// `tokens` without the leading `words`, if it starts with them.
+fn strip<'a>(tokens: &'a [String], words: &[&str]) -> Option<&'a [String]> {
+    starts_with(tokens, words).then(|| &tokens[words.len()..])
+}
+
+/// Split a possibly qualified table name (`schema.table`) from the tokens following it,
+/// dropping the schema.
+fn table_name(tokens: &[String]) -> (String, &[String]) {
+    let mut name = tokens.first().cloned().unwrap_or_default();
+    let mut rest = tokens.get(1..).unwrap_or_default();
+    while let Some(after_dot) = strip(rest, &["."]) {
+        name = after_dot.first().cloned().unwrap_or_default();
+        rest = after_dot.get(1..).unwrap_or_default();
+    }
+    (name, rest)
+}
+
+/// Split the clauses of an `ALTER TABLE` on the commas outside of parentheses.
+fn clauses(tokens: &[String]) -> Vec<&[String]> {
+    let mut clauses = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                clauses.push(&tokens[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    clauses.push(&tokens[start..]);
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LintRule;
+    use crate::Backend;
+
+    fn rules(sql: &str, backend: Backend) -> Result<Vec<(usize, LintRule)>, crate::Error> {
+        let (statements, allowed) = super::split(sql, backend)?;
+        Ok(super::check(&statements, backend)
+            .into_iter()
+            .filter(|(_, rule)| !allowed.contains(rule))
+            .map(|(statement, rule)| (statement.line, rule))
+            .collect())
+    }
+
+    #[test]
+    fn test_postgres_rules() -> Result<(), crate::Error> {
+        let sql = "
+-- Adding a NOT NULL column; is fine with a default
+ALTER TABLE users ADD COLUMN active boolean NOT NULL DEFAULT true;
+ALTER TABLE users ADD COLUMN role text NOT NULL;
+CREATE INDEX users_email ON public.users (email);
+CREATE INDEX CONCURRENTLY users_role ON users (role);
+ALTER TABLE users ALTER COLUMN id TYPE bigint, DROP COLUMN legacy_id;
+ALTER TABLE users DROP CONSTRAINT users_pkey, ALTER COLUMN role DROP DEFAULT;
+DROP TABLE IF EXISTS sessions;
+CREATE FUNCTION f() RETURNS trigger AS $body$ BEGIN DROP TABLE x; END; $body$ LANGUAGE plpgsql;
+INSERT INTO logs VALUES ('DROP TABLE users; ALTER TABLE x DROP y');
+INSERT INTO logs VALUES (E'it\\'s; DROP TABLE users'); /* outer /* inner */ DROP TABLE x; */
+";
+        assert_eq!(
+            rules(sql, Backend::Postgres)?,
+            vec![
+                (4, LintRule::AddColumnNotNull),
+                (5, LintRule::CreateIndexNonConcurrently),
+                (7, LintRule::ColumnTypeChange),
+                (7, LintRule::DropColumn),
+                (9, LintRule::DropTable),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_created_in_same_migration() -> Result<(), crate::Error> {
+        let sql = "CREATE TABLE posts (id bigint);
+CREATE INDEX posts_id ON posts (id);
+ALTER TABLE posts ADD COLUMN title text NOT NULL;
+DROP TABLE posts;";
+        assert_eq!(rules(sql, Backend::Postgres)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mysql_rules() -> Result<(), crate::Error> {
+        let sql = "ALTER TABLE `users` MODIFY `email` varchar(512) NOT NULL;
+ALTER TABLE users ADD INDEX users_email (email), ALGORITHM=INPLACE, LOCK=NONE;
+ALTER TABLE users ADD FULLTEXT INDEX users_bio (bio);
+# DROP TABLE users;
+ALTER TABLE users CONVERT TO CHARACTER SET utf8mb4, LOCK = SHARED;
+CREATE INDEX users_name ON users (name);";
+        assert_eq!(
+            rules(sql, Backend::MySql)?,
+            vec![
+                (1, LintRule::ColumnTypeChange),
+                (3, LintRule::MySqlTableLock),
+                (5, LintRule::MySqlTableLock),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_allow_marker() -> Result<(), crate::Error> {
+        let sql = "/* legacy cleanup */
+-- database-schema: allow(drop_column, drop_table)
+ALTER TABLE users DROP COLUMN legacy_id;
+DROP TABLE legacy_users;
+ALTER TABLE users ALTER COLUMN id TYPE bigint;";
+        assert_eq!(
+            rules(sql, Backend::Postgres)?,
+            vec![(5, LintRule::ColumnTypeChange)]
+        );
+        assert!(rules(
+            "-- database-schema: allow(drop_everything)",
+            Backend::Postgres
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint() -> Result<(), crate::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/down-migrations",
+        ));
+        let warnings = super::lint(&super::LintOptions::new(), Backend::Postgres, &migrations)?;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].version(), "20230902000000");
+        assert_eq!(warnings[0].migration(), "20230902000000_add_posts.up.sql");
+        assert_eq!(warnings[0].line(), 5);
+        assert_eq!(
+            warnings[0].to_string(),
+            "20230902000000_add_posts.up.sql line 5: index created without CONCURRENTLY (create_index_non_concurrently): CREATE INDEX users_email ON users (email)"
+        );
+
+        let options = super::LintOptions::new().after("20230902000000");
+        assert!(super::lint(&options, Backend::Postgres, &migrations)?.is_empty());
+        let options = super::LintOptions::new().allow(LintRule::CreateIndexNonConcurrently);
+        assert!(super::lint(&options, Backend::Postgres, &migrations)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! Loading a dump back into a database, like `rails db:schema:load`.
+//!
+//! The dump is split into statements here rather than handed to `psql` or `mysql`, so
+//! that the same rules apply to every backend and every failing statement is reported
+//! with the line it starts on. The splitter is the one the linter uses too.
+
+use crate::{error::Error, Backend};
+
+/// A statement of a dump that failed to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementError {
+    pub(crate) line: usize,
+    pub(crate) statement: String,
+    pub(crate) message: String,
+}
+
+impl StatementError {
+    /// Line of the dump where the statement starts, from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The statement, as it was run.
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+
+    /// The error reported by the database.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for StatementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let first_line = self.statement.lines().next().unwrap_or_default();
+        write!(f, "line {}: {} ({first_line})", self.line, self.message)
+    }
+}
+
+/// Run the statements of `sql` on `conn` one at a time, going on after failures like
+/// `psql` does. Returns how many statements ran, or the ones that failed.
+pub(crate) async fn run<C: crate::plain::Execute>(
+    conn: &mut C,
+    sql: &str,
+    backend: Backend,
+) -> Result<usize, Error> {
+    let statements = crate::sql::split(sql, backend)
+        .into_iter()
+        .filter(|statement| statement.has_code())
+        .collect::<Vec<_>>();
+    let mut errors = Vec::new();
+    for statement in &statements {
+        tracing::trace!(line = statement.line, "Loading statement");
+        let sql = statement.sql();
+        if let Err(error) = conn.execute_sql(&sql).await {
+            tracing::warn!(line = statement.line, %error, "Statement failed to load");
+            errors.push(StatementError {
+                line: statement.line,
+                statement: sql,
+                message: error.to_string(),
+            });
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(statements.len()),
+        false => Err(Error::LoadFailed(errors)),
+    }
+}
@@ -3,10 +3,14 @@
 //! It provides the following macros:
 //!
 //! * `generate!` - Generate a `destination_path` file using migrations from the provided
-//! `migrations_path` folder.
+//!   `migrations_path` folder.
 //!
 //! * `generate_using_defaults!` - Generate a `./structure.sql` file using migrations
-//! from the `./migrations` folder.
+//!   from the `./migrations` folder.
+//!
+//! Both skip the dump when the migrations didn't change since the last one (see
+//! [`DatabaseSchemaBuilder::skip_unchanged`](crate::DatabaseSchemaBuilder::skip_unchanged)),
+//! so they can run on every build. Set `DATABASE_SCHEMA_FORCE=1` to dump anyway.
 
 /// Generate a `destination_path` file using migrations from the provided
 /// `migrations_path` folder.
@@ -40,6 +44,23 @@ pub fn __generate_within_runtime(
     run_runtime(migrations_path, destination_path);
 }
 
+/// Dump unless nothing changed since the last dump, or `DATABASE_SCHEMA_FORCE` is set.
+async fn generate(
+    migrations_path: std::path::PathBuf,
+    destination_path: std::path::PathBuf,
+) -> Result<(), crate::Error> {
+    let force = std::env::var("DATABASE_SCHEMA_FORCE")
+        .is_ok_and(|force| !matches!(force.as_str(), "" | "0" | "false"));
+    crate::DatabaseSchemaBuilder::new()
+        .migrations_dir(migrations_path)?
+        .destination_path(destination_path)
+        .skip_unchanged(true)
+        .force(force)
+        .build()
+        .dump()
+        .await
+}
+
 fn run_runtime(migrations_path: std::path::PathBuf, destination_path: std::path::PathBuf) {
     #[cfg(feature = "runtime-tokio")]
     {
@@ -47,14 +68,12 @@ fn run_runtime(migrations_path: std::path::PathBuf, destination_path: std::path:
         let local = tokio::task::LocalSet::new();
         local
             .block_on(&rt, async {
-                crate::generate(None, migrations_path, destination_path).await
+                generate(migrations_path, destination_path).await
             })
             .expect("could not run tokio runtime");
     }
 
     #[cfg(feature = "runtime-async-std")]
-    async_std::task::block_on(async {
-        crate::generate(None, migrations_path, destination_path).await
-    })
-    .expect("could not run async-std runtime");
+    async_std::task::block_on(async { generate(migrations_path, destination_path).await })
+        .expect("could not run async-std runtime");
 }
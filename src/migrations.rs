@@ -0,0 +1,683 @@
+//! Where the migrations come from, and helpers to inspect them without running them.
+//!
+//! Both `sqlx` (`<version>_<description>.sql`, optionally suffixed with `.up.sql` and
+//! `.down.sql`) and `diesel` (`<version>_<name>/up.sql` and `down.sql`) directory layouts
+//! are supported, as well as migrations embedded at compile time by `sqlx::migrate!()` or
+//! `diesel_migrations::embed_migrations!()`, and plain `.sql` files run without either
+//! framework's migrations (see [`PlainMigrations`](crate::plain::PlainMigrations)).
+
+use sha2::{Digest, Sha256};
+
+/// The migrations to run before dumping the schema.
+#[derive(Clone)]
+pub(crate) enum MigrationSource {
+    /// A migrations directory, read when dumping.
+    #[cfg_attr(feature = "cli", allow(dead_code))]
+    Directory(std::path::PathBuf),
+
+    /// Migrations embedded with `sqlx::migrate!()`.
+    #[cfg(feature = "sqlx")]
+    Embedded(&'static sqlx::migrate::Migrator),
+
+    /// Migrations embedded with `diesel_migrations::embed_migrations!()`.
+    #[cfg(feature = "diesel")]
+    Embedded(&'static diesel_migrations::EmbeddedMigrations),
+
+    /// A directory of plain SQL migrations, applied and recorded by us.
+    Plain(crate::plain::PlainMigrations),
+}
+
+impl Default for MigrationSource {
+    #[cfg(any(feature = "sqlx", feature = "diesel"))]
+    fn default() -> Self {
+        Self::Directory(std::path::PathBuf::new())
+    }
+
+    #[cfg(feature = "cli")]
+    fn default() -> Self {
+        Self::Plain(crate::plain::PlainMigrations::new("migrations"))
+    }
+}
+
+impl std::fmt::Debug for MigrationSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Directory(path) => f.debug_tuple("Directory").field(path).finish(),
+            // The embedded migrations carry their whole SQL, so only list the versions.
+            #[cfg(any(feature = "sqlx", feature = "diesel"))]
+            Self::Embedded(_) => f
+                .debug_tuple("Embedded")
+                .field(&self.versions().unwrap_or_default())
+                .finish(),
+            Self::Plain(plain) => f.debug_tuple("Plain").field(plain).finish(),
+        }
+    }
+}
+
+impl MigrationSource {
+    /// Return the versions of all the migrations, sorted in the order they are applied.
+    pub(crate) fn versions(&self) -> Result<Vec<String>, crate::error::Error> {
+        match self {
+            Self::Directory(path) => versions(path),
+            #[cfg(feature = "sqlx")]
+            Self::Embedded(migrator) => Ok(sorted(
+                migrator
+                    .iter()
+                    .filter(|migration| !migration.migration_type.is_down_migration())
+                    .map(|migration| migration.version.to_string())
+                    .collect(),
+            )),
+            #[cfg(feature = "diesel")]
+            Self::Embedded(migrations) => Ok(sorted(
+                embedded_names(migrations)
+                    .iter()
+                    .filter_map(|name| version_from_name(name))
+                    .collect(),
+            )),
+            // Already in the order they are applied, which isn't the lexical one.
+            Self::Plain(plain) => Ok(plain
+                .migrations()?
+                .into_iter()
+                .map(|migration| migration.version)
+                .collect()),
+        }
+    }
+
+    /// Hash the migrations, see [`checksum`].
+    ///
+    /// Embedded migrations don't know the files they were read from, so their checksum
+    /// differs from the one of the same directory. `diesel` doesn't expose the SQL of
+    /// embedded migrations either, so for it only their names are hashed.
+    pub(crate) fn checksum(&self) -> Result<String, crate::error::Error> {
+        match self {
+            Self::Directory(path) => checksum(path),
+            Self::Plain(plain) => checksum(&plain.dir),
+            #[cfg(feature = "sqlx")]
+            Self::Embedded(migrator) => {
+                let mut hasher = Sha256::new();
+                for migration in migrator.iter() {
+                    hasher.update(migration.version.to_string().as_bytes());
+                    hasher.update([0]);
+                    hasher.update(migration.description.as_bytes());
+                    hasher.update([0]);
+                    hasher.update(migration.migration_type.label().as_bytes());
+                    hasher.update([0]);
+                    hasher.update(migration.sql.as_bytes());
+                    hasher.update([0]);
+                }
+                Ok(hex(&hasher.finalize()))
+            }
+            #[cfg(feature = "diesel")]
+            Self::Embedded(migrations) => {
+                let mut hasher = Sha256::new();
+                for name in embedded_names(migrations) {
+                    hasher.update(name.as_bytes());
+                    hasher.update([0]);
+                }
+                Ok(hex(&hasher.finalize()))
+            }
+        }
+    }
+
+    /// The SQL of every up migration, in the order they are applied.
+    pub(crate) fn up_migrations(&self) -> Result<Vec<UpMigration>, crate::error::Error> {
+        match self {
+            Self::Directory(path) => up_migrations(path),
+            Self::Plain(plain) => plain.migrations(),
+            #[cfg(feature = "sqlx")]
+            Self::Embedded(migrator) => Ok(migrator
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+                .map(|migration| UpMigration {
+                    version: migration.version.to_string(),
+                    name: migration.description.to_string(),
+                    sql: migration.sql.to_string(),
+                })
+                .collect()),
+            #[cfg(feature = "diesel")]
+            Self::Embedded(_) => Err(crate::error::Error::MigrationSourceError(String::from(
+                "the SQL of embedded diesel migrations isn't available, use a migrations directory",
+            ))),
+        }
+    }
+
+    /// Run the pending migrations on `conn`, only up to the `target` version (included)
+    /// if set.
+    #[cfg(feature = "sqlx")]
+    pub(crate) async fn run<C>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+    ) -> Result<(), crate::error::Error>
+    where
+        C: sqlx::migrate::Migrate + crate::plain::Execute,
+    {
+        match (self, target) {
+            (Self::Plain(plain), _) => crate::plain::migrate(conn, plain, target).await?,
+            (Self::Directory(path), None) => sqlx::migrate::Migrator::new(path.as_path())
+                .await
+                .map_err(sqlx::Error::from)?
+                .run_direct(conn)
+                .await
+                .map_err(sqlx::Error::from)?,
+            (Self::Embedded(migrator), None) => {
+                migrator.run_direct(conn).await.map_err(sqlx::Error::from)?
+            }
+            (_, Some(target)) => {
+                let migrations = self.sqlx_migrations().await?;
+                for migration in pending_sqlx_migrations(conn, &migrations, Some(target)).await? {
+                    let _ = conn.apply(migration).await.map_err(sqlx::Error::from)?;
+                }
+                conn.unlock().await.map_err(sqlx::Error::from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the pending migrations on `conn`, only up to the `target` version (included)
+    /// if set.
+    #[cfg(feature = "diesel")]
+    pub(crate) async fn run<DB, C>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+    ) -> Result<(), crate::error::Error>
+    where
+        DB: diesel::backend::Backend,
+        C: diesel_migrations::MigrationHarness<DB> + crate::plain::Execute,
+    {
+        use diesel_migrations::MigrationHarness;
+
+        let harness_error = |error: Box<dyn std::error::Error + Send + Sync>| {
+            crate::error::Error::MigrationHarnessError(error.to_string())
+        };
+        match (self, target) {
+            (Self::Plain(plain), _) => crate::plain::migrate(conn, plain, target).await,
+            (_, None) => {
+                let _ = diesel_migrations::HarnessWithOutput::write_to_stdout(conn)
+                    .run_pending_migrations(self.diesel_migrations()?)
+                    .map_err(harness_error)?;
+                Ok(())
+            }
+            (_, Some(target)) => {
+                let pending = conn
+                    .pending_migrations(self.diesel_migrations()?)
+                    .map_err(harness_error)?;
+                for migration in pending.iter().filter(|migration| {
+                    version_order(&migration.name().version().to_string()) <= version_order(target)
+                }) {
+                    let _ = conn.run_migration(&**migration).map_err(harness_error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Run the pending migrations on `conn`, only up to the `target` version (included)
+    /// if set.
+    #[cfg(feature = "cli")]
+    pub(crate) async fn run<C: crate::plain::Execute>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+    ) -> Result<(), crate::error::Error> {
+        crate::plain::migrate(conn, self.plain()?, target).await
+    }
+
+    /// Record the migrations as applied on `conn` without running them, only up to the
+    /// `target` version (included) if set, for a database loaded from their dump. Returns
+    /// how many were recorded.
+    #[cfg(feature = "sqlx")]
+    pub(crate) async fn record<C>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+        backend: crate::Backend,
+    ) -> Result<usize, crate::error::Error>
+    where
+        C: sqlx::migrate::Migrate + crate::plain::Execute,
+    {
+        if let Self::Plain(plain) = self {
+            return crate::plain::record(conn, plain, target).await;
+        }
+        let migrations = self.sqlx_migrations().await?;
+        let pending = pending_sqlx_migrations(conn, &migrations, target).await?;
+        for migration in &pending {
+            conn.execute_sql(&format!(
+                "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES ({}, '{}', TRUE, {}, 0)",
+                migration.version,
+                migration.description.replace('\'', "''"),
+                checksum_literal(&migration.checksum, backend),
+            ))
+            .await?;
+        }
+        conn.unlock().await.map_err(sqlx::Error::from)?;
+        Ok(pending.len())
+    }
+
+    /// Record the migrations as applied on `conn` without running them, only up to the
+    /// `target` version (included) if set, for a database loaded from their dump. Returns
+    /// how many were recorded.
+    #[cfg(feature = "diesel")]
+    pub(crate) async fn record<DB, C>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+        backend: crate::Backend,
+    ) -> Result<usize, crate::error::Error>
+    where
+        DB: diesel::backend::Backend,
+        C: diesel_migrations::MigrationHarness<DB> + crate::plain::Execute,
+    {
+        let _ = backend;
+        if let Self::Plain(plain) = self {
+            return crate::plain::record(conn, plain, target).await;
+        }
+        let versions = conn
+            .pending_migrations(self.diesel_migrations()?)
+            .map_err(|error| crate::error::Error::MigrationHarnessError(error.to_string()))?
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .filter(|version| {
+                target.is_none_or(|target| version_order(version) <= version_order(target))
+            })
+            .collect::<Vec<String>>();
+        for version in &versions {
+            conn.execute_sql(&format!(
+                "INSERT INTO __diesel_schema_migrations (version) VALUES ('{version}')"
+            ))
+            .await?;
+        }
+        Ok(versions.len())
+    }
+
+    /// Record the migrations as applied on `conn` without running them, only up to the
+    /// `target` version (included) if set, for a database loaded from their dump. Returns
+    /// how many were recorded.
+    #[cfg(feature = "cli")]
+    pub(crate) async fn record<C: crate::plain::Execute>(
+        &self,
+        conn: &mut C,
+        target: Option<&str>,
+        backend: crate::Backend,
+    ) -> Result<usize, crate::error::Error> {
+        let _ = backend;
+        crate::plain::record(conn, self.plain()?, target).await
+    }
+
+    /// The table recording the applied migrations.
+    pub(crate) fn table(&self) -> Option<&str> {
+        match self {
+            Self::Plain(plain) => plain.table.as_deref(),
+            #[cfg(feature = "sqlx")]
+            _ => Some("_sqlx_migrations"),
+            #[cfg(feature = "diesel")]
+            _ => Some("__diesel_schema_migrations"),
+            #[cfg(feature = "cli")]
+            Self::Directory(_) => None,
+        }
+    }
+
+    /// The plain migrations, the only ones run without `sqlx` or `diesel`.
+    #[cfg(feature = "cli")]
+    pub(crate) fn plain(&self) -> Result<&crate::plain::PlainMigrations, crate::error::Error> {
+        match self {
+            Self::Plain(plain) => Ok(plain),
+            Self::Directory(_) => Err(crate::error::Error::MigrationSourceError(String::from(
+                "sqlx and diesel migrations need their feature, the cli feature only runs plain SQL migrations",
+            ))),
+        }
+    }
+
+    /// All the migrations, up and down, in the order they are applied.
+    #[cfg(feature = "sqlx")]
+    pub(crate) async fn sqlx_migrations(
+        &self,
+    ) -> Result<Vec<sqlx::migrate::Migration>, crate::error::Error> {
+        Ok(match self {
+            Self::Directory(path) => sqlx::migrate::Migrator::new(path.as_path())
+                .await
+                .map_err(sqlx::Error::from)?
+                .iter()
+                .cloned()
+                .collect(),
+            Self::Embedded(migrator) => migrator.iter().cloned().collect(),
+            Self::Plain(_) => return Err(not_framework_migrations()),
+        })
+    }
+
+    /// The migrations, ready to be given to a `diesel` migration harness.
+    #[cfg(feature = "diesel")]
+    pub(crate) fn diesel_migrations(&self) -> Result<DieselMigrations, crate::error::Error> {
+        Ok(match self {
+            Self::Directory(path) => DieselMigrations::Directory(
+                diesel_migrations::FileBasedMigrations::from_path(path)?,
+            ),
+            Self::Embedded(migrations) => DieselMigrations::Embedded(migrations),
+            Self::Plain(_) => return Err(not_framework_migrations()),
+        })
+    }
+}
+
+/// Plain migrations are run by [`crate::plain`], never by the framework.
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+fn not_framework_migrations() -> crate::error::Error {
+    crate::error::Error::MigrationSourceError(format!(
+        "plain SQL migrations can't be run as {} migrations",
+        crate::FRAMEWORK
+    ))
+}
+
+/// Lock the database and check it the way `Migrator::run_direct` does before applying
+/// migrations one at a time: refuse a dirty database, and applied migrations that are
+/// missing or were changed since.
+///
+/// Returns the pending up migrations, only up to the `target` version (included) if set.
+/// The database stays locked until `unlock` is called.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn pending_sqlx_migrations<'m, C: sqlx::migrate::Migrate>(
+    conn: &mut C,
+    migrations: &'m [sqlx::migrate::Migration],
+    target: Option<&str>,
+) -> Result<Vec<&'m sqlx::migrate::Migration>, sqlx::Error> {
+    use sqlx::migrate::MigrateError;
+
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version).into());
+    }
+    let applied = conn.list_applied_migrations().await?;
+    if let Some(missing) = applied.iter().find(|applied| {
+        !migrations
+            .iter()
+            .any(|migration| migration.version == applied.version)
+    }) {
+        return Err(MigrateError::VersionMissing(missing.version).into());
+    }
+
+    let mut pending = Vec::new();
+    for migration in migrations
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        match applied
+            .iter()
+            .find(|applied| applied.version == migration.version)
+        {
+            Some(applied) if applied.checksum != migration.checksum => {
+                return Err(MigrateError::VersionMismatch(migration.version).into())
+            }
+            Some(_) => {}
+            None if target.is_none_or(|target| {
+                version_order(&migration.version.to_string()) <= version_order(target)
+            }) =>
+            {
+                pending.push(migration)
+            }
+            None => {}
+        }
+    }
+    Ok(pending)
+}
+
+/// The SQL of an up migration.
+#[derive(Debug, Clone)]
+pub(crate) struct UpMigration {
+    pub(crate) version: String,
+    /// File or directory name (description with embedded `sqlx` migrations).
+    pub(crate) name: String,
+    pub(crate) sql: String,
+}
+
+/// Read the up migrations of the `migrations_path` directory, sorted by version.
+fn up_migrations(
+    migrations_path: &std::path::Path,
+) -> Result<Vec<UpMigration>, crate::error::Error> {
+    let mut migrations = Vec::new();
+    for entry in std::fs::read_dir(migrations_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = if entry.file_type()?.is_dir() {
+            entry.path().join("up.sql")
+        } else if name.ends_with(".sql") && !name.ends_with(".down.sql") {
+            entry.path()
+        } else {
+            continue;
+        };
+        let Some(version) = version_from_name(&name) else {
+            continue;
+        };
+        if path.is_file() {
+            migrations.push(UpMigration {
+                version,
+                name,
+                sql: std::fs::read_to_string(path)?,
+            });
+        }
+    }
+    migrations.sort_by(|a, b| version_order(&a.version).cmp(&version_order(&b.version)));
+    Ok(migrations)
+}
+
+/// Dumps the schema of the database between migration steps.
+pub(crate) trait Snapshot<C> {
+    /// Dump the schema, as it would end up in the destination file.
+    async fn snapshot(&self, conn: &mut C) -> Result<String, crate::error::Error>;
+}
+
+/// Either kind of `diesel` migrations, as a single migration source.
+#[cfg(feature = "diesel")]
+pub(crate) enum DieselMigrations {
+    Directory(diesel_migrations::FileBasedMigrations),
+    Embedded(&'static diesel_migrations::EmbeddedMigrations),
+}
+
+#[cfg(feature = "diesel")]
+impl<DB: diesel::backend::Backend> diesel::migration::MigrationSource<DB> for DieselMigrations {
+    fn migrations(
+        &self,
+    ) -> diesel::migration::Result<Vec<Box<dyn diesel::migration::Migration<DB>>>> {
+        match self {
+            Self::Directory(migrations) => migrations.migrations(),
+            Self::Embedded(migrations) => migrations.migrations(),
+        }
+    }
+}
+
+/// Names of the embedded `diesel` migrations, such as `2023-08-27-215620_create_users`.
+#[cfg(feature = "diesel")]
+fn embedded_names(migrations: &diesel_migrations::EmbeddedMigrations) -> Vec<String> {
+    // The names don't depend on the backend, any enabled one will do.
+    #[cfg(feature = "sqlite")]
+    type Db = diesel::sqlite::Sqlite;
+    #[cfg(all(feature = "postgres", not(feature = "sqlite")))]
+    type Db = diesel::pg::Pg;
+    #[cfg(all(feature = "mysql", not(any(feature = "sqlite", feature = "postgres"))))]
+    type Db = diesel::mysql::Mysql;
+
+    diesel::migration::MigrationSource::<Db>::migrations(migrations)
+        .unwrap_or_default()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect()
+}
+
+/// Return the versions of all the migrations found in `migrations_path`, sorted in the
+/// order they are applied.
+///
+/// `diesel` stores versions without the dashes, so `2023-08-27-215620` becomes
+/// `20230827215620`, matching what ends up in the migrations table.
+pub(crate) fn versions<P: AsRef<std::path::Path>>(
+    migrations_path: P,
+) -> Result<Vec<String>, crate::error::Error> {
+    let versions = std::fs::read_dir(migrations_path)?
+        .map(|entry| {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_migration = if entry.file_type()?.is_dir() {
+                entry.path().join("up.sql").is_file()
+            } else {
+                name.ends_with(".sql")
+            };
+            Ok(is_migration.then(|| version_from_name(&name)).flatten())
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<String>, std::io::Error>>()?;
+    Ok(sorted(versions))
+}
+
+/// Sort `versions` in the order they are applied, without duplicates (the up and down
+/// migrations of a version).
+fn sorted(mut versions: Vec<String>) -> Vec<String> {
+    versions.sort_by(|a, b| version_order(a).cmp(&version_order(b)));
+    versions.dedup();
+    versions
+}
+
+/// Hash the contents of every file under `migrations_path`, including their paths
+/// relative to it, so that renaming a migration changes the result.
+///
+/// The result is a lowercase hex encoded SHA-256 digest.
+pub(crate) fn checksum<P: AsRef<std::path::Path>>(
+    migrations_path: P,
+) -> Result<String, crate::error::Error> {
+    let root = migrations_path.as_ref();
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(std::fs::read(&file)?);
+        hasher.update([0]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SQL literal of the checksum of a `sqlx` migration, as stored in `_sqlx_migrations`.
+#[cfg(feature = "sqlx")]
+pub(crate) fn checksum_literal(checksum: &[u8], backend: crate::Backend) -> String {
+    match backend {
+        crate::Backend::Postgres => format!("decode('{}', 'hex')", hex(checksum)),
+        _ => format!("X'{}'", hex(checksum)),
+    }
+}
+
+fn collect_files(
+    dir: &std::path::Path,
+    files: &mut Vec<std::path::PathBuf>,
+) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Sort key comparing versions as numbers of any length, so that `10` comes after `9`.
+pub(crate) fn version_order(version: &str) -> (usize, &str) {
+    let version = version.trim_start_matches('0');
+    (version.len(), version)
+}
+
+pub(crate) fn version_from_name(name: &str) -> Option<String> {
+    let version = name
+        .split_once('_')
+        .map_or(name, |(version, _)| version)
+        .replace('-', "");
+    (!version.is_empty() && version.chars().all(|c| c.is_ascii_digit())).then_some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_versions() -> Result<(), crate::error::Error> {
+        assert_eq!(
+            super::versions("./fixtures/sqlx/sqlite/migrations")?,
+            vec![String::from("20230827160610")]
+        );
+        assert_eq!(
+            super::versions("./fixtures/diesel/sqlite/migrations")?,
+            vec![String::from("20230827215620")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpadded_versions() -> Result<(), crate::error::Error> {
+        let migrations_path = std::env::temp_dir().join("database-schema-unpadded-versions");
+        let _ = std::fs::remove_dir_all(&migrations_path);
+        std::fs::create_dir_all(&migrations_path)?;
+        for name in ["1_users.sql", "2_posts.sql", "10_comments.sql"] {
+            std::fs::write(migrations_path.join(name), "SELECT 1;")?;
+        }
+        let versions = super::versions(&migrations_path)?;
+        let up_migrations = super::up_migrations(&migrations_path)?;
+        std::fs::remove_dir_all(&migrations_path)?;
+        assert_eq!(versions, vec!["1", "2", "10"]);
+        assert_eq!(
+            up_migrations
+                .iter()
+                .map(|migration| migration.version.as_str())
+                .collect::<Vec<_>>(),
+            versions
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_order() {
+        let mut versions = vec!["10", "9", "0011", "20230827160610", "1"];
+        versions.sort_by_key(|version| super::version_order(version));
+        assert_eq!(versions, vec!["1", "9", "10", "0011", "20230827160610"]);
+    }
+
+    #[test]
+    fn test_checksum_is_stable() -> Result<(), crate::error::Error> {
+        let first = super::checksum("./fixtures/diesel/sqlite/migrations")?;
+        let second = super::checksum("./fixtures/diesel/sqlite/migrations")?;
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 64);
+        assert_ne!(first, super::checksum("./fixtures/sqlx/sqlite/migrations")?);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlx")]
+    const EMBEDDED_PATH: &str = "./fixtures/sqlx/postgres/migrations";
+    #[cfg(feature = "sqlx")]
+    static EMBEDDED: sqlx::migrate::Migrator =
+        sqlx::migrate!("./fixtures/sqlx/postgres/migrations");
+    #[cfg(feature = "diesel")]
+    const EMBEDDED_PATH: &str = "./fixtures/diesel/postgres/migrations";
+    #[cfg(feature = "diesel")]
+    static EMBEDDED: diesel_migrations::EmbeddedMigrations =
+        diesel_migrations::embed_migrations!("./fixtures/diesel/postgres/migrations");
+
+    #[cfg(any(feature = "sqlx", feature = "diesel"))]
+    #[test]
+    fn test_embedded() -> Result<(), crate::error::Error> {
+        use super::MigrationSource;
+        let embedded = MigrationSource::Embedded(&EMBEDDED);
+        let directory = MigrationSource::Directory(EMBEDDED_PATH.into());
+        assert_eq!(embedded.versions()?, directory.versions()?);
+        assert_eq!(embedded.checksum()?.len(), 64);
+        assert_ne!(embedded.checksum()?, directory.checksum()?);
+        assert_eq!(
+            format!("{embedded:?}"),
+            format!("Embedded({:?})", embedded.versions()?)
+        );
+        Ok(())
+    }
+}
@@ -8,38 +8,439 @@ use crate::error::Error;
 
 mod options;
 
-use options::MySqlConnectOptions;
+pub use options::{MySqlConnectOptions, MySqlSslMode};
 
-#[allow(unused_results)]
-#[cfg(any(feature = "sqlx", feature = "diesel"))]
-pub(crate) async fn write_structure_sql<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn write_structure_sql<Q: AsRef<std::path::Path>>(
     connection_url: &str,
-    migrations_path: P,
+    migrations: &crate::migrations::MigrationSource,
     destination_path: Q,
+    options: &crate::DumpOptions<'_>,
+    mysqldump: Option<&std::path::Path>,
+    strict: bool,
 ) -> Result<(), Error> {
-    let options = extract_connect_options(connection_url)?;
+    let connect_options = extract_connect_options(connection_url, strict)?;
+    let connection_url = &framework_connection_url(connection_url)?;
+    let server_version = server_version(connection_url).await?;
+    let snapshot = MySqlDump {
+        options: connect_options,
+        mysqldump: Mysqldump::find(mysqldump, &server_version)?,
+        exclude_tables: options.exclude_tables.to_vec(),
+    };
+
+    match options.history_dir {
+        Some(history_dir) => {
+            let mut conn = connect(connection_url).await?;
+            crate::history::migrate(
+                &mut conn,
+                migrations,
+                &snapshot,
+                history_dir,
+                options.target,
+            )
+            .await?
+        }
+        None => migrate(connection_url, migrations, options.target).await?,
+    }
+    dump(
+        &snapshot.options,
+        &snapshot.mysqldump,
+        options.exclude_tables,
+        destination_path.as_ref(),
+    )
+    .await?;
+    if !options.data_tables.is_empty() {
+        let data = dump_data(&snapshot.options, &snapshot.mysqldump, options.data_tables).await?;
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(destination_path.as_ref())?;
+        std::io::Write::write_all(&mut file, format!("\n{data}").as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Check that the down migrations restore the schema, comparing `mysqldump` outputs.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn verify_down_migrations(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    mysqldump: Option<&std::path::Path>,
+    strict: bool,
+) -> Result<crate::DownMigrationReport, Error> {
+    let options = extract_connect_options(connection_url, strict)?;
+    let connection_url = &framework_connection_url(connection_url)?;
+    let server_version = server_version(connection_url).await?;
+    let snapshot = MySqlDump {
+        options,
+        mysqldump: Mysqldump::find(mysqldump, &server_version)?,
+        exclude_tables: Vec::new(),
+    };
+
+    let mut conn = connect(connection_url).await?;
+    crate::verify::verify(&mut conn, migrations, &snapshot).await
+}
+
+/// Migrate up to `version` and dump the schema, without the migrations table, as a
+/// baseline migration.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn baseline(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    version: &str,
+    mysqldump: Option<&std::path::Path>,
+    strict: bool,
+) -> Result<String, Error> {
+    let options = extract_connect_options(connection_url, strict)?;
+    let connection_url = &framework_connection_url(connection_url)?;
+    let server_version = server_version(connection_url).await?;
+    let snapshot = MySqlDump {
+        options,
+        mysqldump: Mysqldump::find(mysqldump, &server_version)?,
+        exclude_tables: Vec::from_iter(migrations.table().map(str::to_owned)),
+    };
+
+    let mut conn = connect(connection_url).await?;
+    migrations.run(&mut conn, Some(version)).await?;
+    crate::migrations::Snapshot::snapshot(&snapshot, &mut conn).await
+}
+
+/// Record the baseline `version` as applied on a database migrated before the squash.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn adopt_baseline(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    version: &str,
+) -> Result<bool, Error> {
+    let mut conn = connect(&framework_connection_url(connection_url)?).await?;
+    crate::squash::adopt(&mut conn, migrations, version, crate::Backend::MySql).await
+}
+
+/// Dump the schema of the database as it is, without running the migrations.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn structure(
+    connection_url: &str,
+    mysqldump: Option<&std::path::Path>,
+    strict: bool,
+) -> Result<String, Error> {
+    let options = extract_connect_options(connection_url, strict)?;
+    let server_version = server_version(&framework_connection_url(connection_url)?).await?;
+    let snapshot = MySqlDump {
+        options,
+        mysqldump: Mysqldump::find(mysqldump, &server_version)?,
+        exclude_tables: Vec::new(),
+    };
+    crate::migrations::Snapshot::snapshot(&snapshot, &mut ()).await
+}
+
+/// Run the statements of the dump `sql`, then record the `migrations` it was dumped
+/// from as applied.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn load(
+    connection_url: &str,
+    sql: &str,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
+) -> Result<usize, Error> {
+    let framework_url = framework_connection_url(connection_url)?;
+    let mut conn = connect(&framework_url).await?;
+    let statements = crate::load::run(&mut conn, sql, crate::Backend::MySql).await?;
+    // The dump changes session settings, such as `SQL_MODE` or `FOREIGN_KEY_CHECKS`.
+    let mut conn = connect(&framework_url).await?;
+    let _ = migrations
+        .record(&mut conn, target, crate::Backend::MySql)
+        .await?;
+    Ok(statements)
+}
+
+/// Open a connection to run the migrations one at a time.
+#[cfg(feature = "sqlx")]
+async fn connect(connection_url: &str) -> Result<sqlx::MySqlConnection, Error> {
+    use sqlx::{mysql::MySqlConnectOptions, ConnectOptions};
+    use std::str::FromStr;
+
+    Ok(MySqlConnectOptions::from_str(connection_url)?
+        .connect()
+        .await?)
+}
+
+/// Open a connection to run the migrations one at a time.
+#[cfg(feature = "diesel")]
+async fn connect(connection_url: &str) -> Result<diesel::MysqlConnection, Error> {
+    use diesel::Connection;
+
+    Ok(diesel::MysqlConnection::establish(connection_url)?)
+}
+
+/// Open a connection to run the migrations one at a time.
+#[cfg(feature = "cli")]
+async fn connect(connection_url: &str) -> Result<Mysql, Error> {
+    Ok(Mysql {
+        options: extract_connect_options(connection_url, false)?,
+        settings: Vec::new(),
+    })
+}
+
+/// A connection through the `mysql` client, which runs in a process of its own for every
+/// query.
+///
+/// The session settings of a dump (`/*!40101 SET ... */`) would be lost between the
+/// processes, so they are replayed before every query.
+#[cfg(feature = "cli")]
+struct Mysql {
+    options: MySqlConnectOptions,
+    settings: Vec<String>,
+}
+
+#[cfg(feature = "cli")]
+impl Mysql {
+    /// Ends the SQL we run, which the client would otherwise split on every `;`, including
+    /// those of `BEGIN ... END` bodies. The server splits the statements itself.
+    const DELIMITER: &'static str = "$$database_schema$$";
+
+    /// Run `sql`, returning the rows it printed, with their columns separated by tabs.
+    #[allow(unused_results)]
+    async fn query(&self, sql: &str) -> Result<String, Error> {
+        let defaults_file = client_option_file(&self.options)
+            .map(|contents| crate::process::SecretFile::new("cnf", &contents))
+            .transpose()?;
+        let mut cmd = command(
+            &self.options,
+            std::path::Path::new("mysql"),
+            defaults_file.as_ref(),
+        );
+        cmd.arg("--batch").arg("--skip-column-names");
+        if let Some(ref database) = self.options.database {
+            cmd.arg("--database").arg(database);
+        }
+        let mut input = String::new();
+        for setting in &self.settings {
+            input.push_str(&format!("{setting};\n"));
+        }
+        input.push_str(&format!(
+            "DELIMITER {delimiter}\n{sql}\n{delimiter}\n",
+            delimiter = Self::DELIMITER
+        ));
+        crate::process::output(&mut cmd, Some(&input)).await
+    }
+}
+
+#[cfg(feature = "cli")]
+impl crate::plain::Execute for Mysql {
+    async fn execute_sql(&mut self, sql: &str) -> Result<(), Error> {
+        let _ = self.query(sql).await?;
+        let statement = sql.trim().trim_end_matches(';').trim_end();
+        // Settings are conditional comments in dumps, e.g. `/*!40101 SET NAMES utf8 */`.
+        let setting = match statement.strip_prefix("/*!") {
+            Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_digit()),
+            None => statement,
+        };
+        if setting
+            .trim_start()
+            .to_ascii_uppercase()
+            .starts_with("SET ")
+        {
+            self.settings.push(statement.to_owned());
+        }
+        Ok(())
+    }
+
+    async fn fetch_versions(&mut self, query: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .query(query)
+            .await?
+            .lines()
+            .map(|row| row.split('\t').next().unwrap_or_default().to_owned())
+            .collect())
+    }
+}
+
+/// Dumps the schema with `mysqldump`, between migration steps.
+struct MySqlDump {
+    options: MySqlConnectOptions,
+    mysqldump: Mysqldump,
+    exclude_tables: Vec<String>,
+}
+
+/// The `mysqldump` binary to use, and whether it has to skip the column statistics.
+struct Mysqldump {
+    path: std::path::PathBuf,
+    /// MySQL 8 clients read `information_schema.COLUMN_STATISTICS` by default, which
+    /// MySQL 5.7 and MariaDB servers don't have.
+    skip_column_statistics: bool,
+}
+
+impl Mysqldump {
+    /// Find the binary to dump a server running `server_version`, see
+    /// [`DumpTool::find`](crate::binary::DumpTool::find).
+    fn find(configured: Option<&std::path::Path>, server_version: &str) -> Result<Self, Error> {
+        let path = crate::binary::MYSQLDUMP.find(configured, server_version)?;
+        let client_version = std::process::Command::new(&path)
+            .arg("--version")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default();
+        Ok(Self {
+            path,
+            skip_column_statistics: skip_column_statistics(&client_version, server_version),
+        })
+    }
+}
+
+/// Whether the client of `mysqldump --version` printing `client_version` needs
+/// `--column-statistics=0` to dump a server running `server_version`: a MySQL 8 client
+/// with a MySQL 5.7 or MariaDB server. MariaDB clients don't know the option.
+fn skip_column_statistics(client_version: &str, server_version: &str) -> bool {
+    let is_mysql_8 = |version: &str, major: Option<u32>| {
+        !version.contains("MariaDB") && major.is_some_and(|major| major >= 8)
+    };
+    is_mysql_8(
+        client_version,
+        crate::binary::parse_version(client_version).map(|version| crate::binary::major(&version)),
+    ) && !is_mysql_8(server_version, Some(crate::binary::major(server_version)))
+}
+
+impl<C> crate::migrations::Snapshot<C> for MySqlDump {
+    async fn snapshot(&self, _conn: &mut C) -> Result<String, Error> {
+        let file = crate::process::SecretFile::new("sql", "")?;
+        dump(
+            &self.options,
+            &self.mysqldump,
+            &self.exclude_tables,
+            file.path(),
+        )
+        .await?;
+        Ok(std::fs::read_to_string(file.path())?)
+    }
+}
 
-    migrate(connection_url, migrations_path).await?;
+/// Run `mysqldump` with `options`, writing the schema to `destination_path`, without
+/// `exclude_tables`.
+#[allow(unused_results)]
+async fn dump<Q: AsRef<std::path::Path>>(
+    options: &MySqlConnectOptions,
+    mysqldump: &Mysqldump,
+    exclude_tables: &[String],
+    destination_path: Q,
+) -> Result<(), Error> {
+    // The password goes through an option file so that it doesn't show up in the process
+    // arguments.
+    let defaults_file = client_option_file(options)
+        .map(|contents| crate::process::SecretFile::new("cnf", &contents))
+        .transpose()?;
 
-    let mut cmd = std::process::Command::new("mysqldump");
+    let mut cmd = command(options, &mysqldump.path, defaults_file.as_ref());
+    if mysqldump.skip_column_statistics {
+        cmd.arg("--column-statistics=0");
+    }
     cmd.arg("--no-data")
         .arg("--routines")
         .arg("--skip-comments")
         .arg("--result-file")
-        .arg(destination_path.as_ref())
-        .arg("--host")
-        .arg(options.host.clone())
-        .arg("--port")
-        .arg(options.port.to_string())
-        .arg("--user")
+        .arg(destination_path.as_ref());
+    // `--ignore-table` needs the table qualified with its database.
+    for table in exclude_tables {
+        let table = match (table.contains('.'), &options.database) {
+            (true, _) => table.to_owned(),
+            (false, Some(database)) => format!("{database}.{table}"),
+            (false, None) => continue,
+        };
+        cmd.arg(format!("--ignore-table={table}"));
+    }
+    // This must come last because mysqldump expects the database name to be the last
+    // argument
+    if let Some(ref database) = options.database {
+        cmd.arg(database.clone());
+    } else {
+        cmd.arg("--all-databases");
+    }
+
+    crate::process::run(&mut cmd).await?;
+    Ok(())
+}
+
+/// Run `mysqldump` with `options` for the rows of `tables`, as sorted `INSERT`
+/// statements.
+#[allow(unused_results)]
+async fn dump_data(
+    options: &MySqlConnectOptions,
+    mysqldump: &Mysqldump,
+    tables: &[String],
+) -> Result<String, Error> {
+    let Some(ref database) = options.database else {
+        return Err(Error::UriConfiguration(String::from(
+            "a database is needed to dump the data of tables",
+        )));
+    };
+    let defaults_file = client_option_file(options)
+        .map(|contents| crate::process::SecretFile::new("cnf", &contents))
+        .transpose()?;
+    let file = crate::process::SecretFile::new("sql", "")?;
+
+    let mut cmd = command(options, &mysqldump.path, defaults_file.as_ref());
+    if mysqldump.skip_column_statistics {
+        cmd.arg("--column-statistics=0");
+    }
+    cmd.arg("--no-create-info")
+        .arg("--skip-triggers")
+        .arg("--compact")
+        .arg("--skip-extended-insert")
+        .arg("--order-by-primary")
+        .arg("--result-file")
+        .arg(file.path());
+    // The database, then the tables, come last. Tables of other databases can't be
+    // dumped in the same run, so they are listed without their database.
+    cmd.arg(database.clone());
+    for table in tables {
+        match table.split_once('.') {
+            Some((table_database, table)) if table_database == database => cmd.arg(table),
+            Some(_) => return Err(Error::InvalidDataTable(table.clone())),
+            None => cmd.arg(table),
+        };
+    }
+    crate::process::run(&mut cmd).await?;
+
+    Ok(crate::data::sort_inserts(
+        &std::fs::read_to_string(file.path())?,
+        true,
+    ))
+}
+
+/// Start a `mysqldump` command connecting with `options`, reading `defaults_file` if set.
+#[allow(unused_results)]
+fn command(
+    options: &MySqlConnectOptions,
+    mysqldump: &std::path::Path,
+    defaults_file: Option<&crate::process::SecretFile>,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new(mysqldump);
+    // mysqldump requires `--defaults-extra-file` to be the first argument.
+    if let Some(defaults_file) = defaults_file {
+        let mut arg = std::ffi::OsString::from("--defaults-extra-file=");
+        arg.push(defaults_file.path());
+        cmd.arg(arg);
+    }
+
+    // Without an explicit protocol, mysqldump silently uses the default socket when the
+    // host is `localhost`, while the migrations ran over TCP.
+    if let Some(ref socket) = options.socket {
+        cmd.arg("--protocol")
+            .arg("SOCKET")
+            .arg("--socket")
+            .arg(socket);
+    } else {
+        cmd.arg("--protocol")
+            .arg("TCP")
+            .arg("--host")
+            .arg(options.host.clone())
+            .arg("--port")
+            .arg(options.port.to_string());
+    }
+
+    cmd.arg("--user")
         .arg(options.username.clone())
         .arg("--ssl-mode")
         .arg(format!("{}", options.ssl_mode));
 
-    if let Some(ref password) = options.password {
-        cmd.arg("--password").arg(password.clone());
-    }
-
     if let Some(ref ssl_ca) = options.ssl_ca {
         cmd.arg("--ssl-ca").arg(ssl_ca.clone());
     }
@@ -49,20 +450,98 @@ pub(crate) async fn write_structure_sql<P: AsRef<std::path::Path>, Q: AsRef<std:
     if let Some(ref ssl_key) = options.ssl_client_key {
         cmd.arg("--ssl-key").arg(ssl_key.clone());
     }
-    // This must come last because mysqldump expects the database name to be the last
-    // argument
-    if let Some(ref database) = options.database {
-        cmd.arg(database.clone());
+    if let Some(ref tls_version) = options.tls_version {
+        cmd.arg("--tls-version").arg(tls_version);
+    }
+    if let Some(ref charset) = options.charset {
+        cmd.arg("--default-character-set").arg(charset);
+    }
+    if options.compression {
+        cmd.arg("--compress");
+    }
+    cmd
+}
+
+/// Render a MySQL option file passing the password and the connection timeout, which
+/// mysqldump has no command line option for, to the client programs.
+fn client_option_file(options: &MySqlConnectOptions) -> Option<String> {
+    let mut contents = String::new();
+    if let Some(ref password) = options.password {
+        let escaped = password.replace('\\', "\\\\").replace('"', "\\\"");
+        contents.push_str(&format!("password=\"{escaped}\"\n"));
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        contents.push_str(&format!("connect-timeout={}\n", connect_timeout.as_secs()));
+    }
+    (!contents.is_empty()).then(|| format!("[client]\n{contents}"))
+}
+
+/// Socket-only setups often have no host in the URL (`mysql://root@/db?socket=...`),
+/// which neither `url` nor the migration frameworks can parse: use `localhost` instead.
+pub(crate) fn with_default_host(connection_url: &str) -> String {
+    let Some((scheme, rest)) = connection_url.split_once("://") else {
+        return connection_url.to_owned();
+    };
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(end);
+    let (userinfo, host) = match authority.rsplit_once('@') {
+        Some((userinfo, host)) => (format!("{userinfo}@"), host),
+        None => (String::new(), authority),
+    };
+    if host.is_empty() || host.starts_with(':') {
+        format!("{scheme}://{userinfo}localhost{host}{tail}")
     } else {
-        cmd.arg("--all-databases");
+        connection_url.to_owned()
     }
+}
 
-    crate::process::run(&mut cmd).await?;
-    Ok(())
+/// Rewrite the connection URL in the dialect of the migration framework.
+///
+/// `sqlx` reads the socket from `socket` and `diesel` from `unix_socket`, but we accept
+/// both so that the migrations and mysqldump always go through the same socket.
+fn framework_connection_url(connection_url: &str) -> Result<String, Error> {
+    #[cfg(any(feature = "sqlx", feature = "cli"))]
+    const SOCKET: (&str, &str) = ("unix_socket", "socket");
+    #[cfg(feature = "diesel")]
+    const SOCKET: (&str, &str) = ("socket", "unix_socket");
+
+    let mut url = url::Url::parse(&with_default_host(connection_url)).map_err(|e| {
+        Error::UriConfiguration(format!(
+            "invalid MySQL connection URL {}: {e}",
+            crate::process::redact(connection_url)
+        ))
+    })?;
+    let pairs = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let key = if key == SOCKET.0 {
+                SOCKET.1.into()
+            } else {
+                key
+            };
+            (key.into_owned(), value.into_owned())
+        })
+        .collect::<Vec<(String, String)>>();
+    if !pairs.is_empty() {
+        let _ = url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    Ok(url.to_string())
 }
 
-fn extract_connect_options(connection_url: &str) -> Result<MySqlConnectOptions, Error> {
-    let url = url::Url::parse(connection_url).unwrap();
+/// Parse a MySQL connection URL.
+///
+/// Both the `sqlx` and `diesel` spellings of the parameters are understood. Unknown
+/// parameters are ignored, unless `strict` is set.
+pub(crate) fn extract_connect_options(
+    connection_url: &str,
+    strict: bool,
+) -> Result<MySqlConnectOptions, Error> {
+    let url = url::Url::parse(&with_default_host(connection_url)).map_err(|e| {
+        Error::UriConfiguration(format!(
+            "invalid MySQL connection URL {}: {e}",
+            crate::process::redact(connection_url)
+        ))
+    })?;
 
     let mut options = MySqlConnectOptions::new();
 
@@ -93,7 +572,11 @@ fn extract_connect_options(connection_url: &str) -> Result<MySqlConnectOptions,
 
     let path = url.path().trim_start_matches('/');
     if !path.is_empty() {
-        options = options.database(path);
+        options = options.database(
+            &percent_decode_str(path)
+                .decode_utf8()
+                .map_err(Error::UriConfigurationDecoding)?,
+        );
     }
 
     for (key, value) in url.query_pairs().into_iter() {
@@ -110,10 +593,57 @@ fn extract_connect_options(connection_url: &str) -> Result<MySqlConnectOptions,
 
             "sslkey" | "ssl-key" | "ssl_key" => options = options.ssl_client_key(&*value),
 
-            "socket" => {
+            "socket" | "unix_socket" => {
                 options = options.socket(&*value);
             }
 
+            "charset" => options = options.charset(&value),
+
+            "collation" => options = options.collation(&value),
+
+            "compression" => {
+                let enabled = match &*value.to_ascii_lowercase() {
+                    "true" | "1" | "on" => true,
+                    "false" | "0" | "off" => false,
+                    _ => {
+                        return Err(Error::UriConfiguration(format!(
+                            "unknown value {value:?} for `compression`"
+                        )))
+                    }
+                };
+                options = options.compression(enabled);
+            }
+
+            "connect_timeout" | "connect-timeout" => {
+                let seconds = value.parse().map_err(|_| {
+                    Error::UriConfiguration(format!(
+                        "unknown value {value:?} for `connect_timeout`, expected seconds"
+                    ))
+                })?;
+                options = options.connect_timeout(std::time::Duration::from_secs(seconds));
+            }
+
+            "tls-version" | "tls_version" => {
+                if let Some(version) = value
+                    .split(',')
+                    .find(|version| !TLS_VERSIONS.contains(&version.trim()))
+                {
+                    return Err(Error::UriConfiguration(format!(
+                        "unknown value {version:?} for `tls-version`, expected one of {}",
+                        TLS_VERSIONS.join(", ")
+                    )));
+                }
+                options = options.tls_version(&value);
+            }
+
+            _ if SQLX_PARAMETERS.contains(&&*key) => {}
+
+            _ if strict => {
+                return Err(Error::UriConfiguration(format!(
+                    "unknown parameter `{key}` in MySQL connection URL"
+                )));
+            }
+
             _ => {}
         }
     }
@@ -121,47 +651,324 @@ fn extract_connect_options(connection_url: &str) -> Result<MySqlConnectOptions,
     Ok(options)
 }
 
+/// Parameters `sqlx` 0.7 accepts in MySQL connection URLs which only configure its own
+/// connections, and are irrelevant to mysqldump. Those of later versions (`timezone`,
+/// `pipes-as-concat`...) would be silently dropped by both, so strict mode rejects them.
+const SQLX_PARAMETERS: [&str; 1] = ["statement-cache-capacity"];
+
+/// Protocols accepted by `--tls-version`.
+const TLS_VERSIONS: [&str; 4] = ["TLSv1", "TLSv1.1", "TLSv1.2", "TLSv1.3"];
+
+/// Open and close a connection, to check that the server accepts them.
 #[cfg(feature = "sqlx")]
-async fn migrate<P: AsRef<std::path::Path>>(
-    connection_url: &str,
-    migrations_path: P,
-) -> Result<(), sqlx::Error> {
-    use sqlx::{
-        migrate::{Migrate, Migrator},
-        mysql::MySqlConnectOptions,
-        ConnectOptions,
-    };
+pub(crate) async fn ping(connection_url: &str) -> Result<(), Error> {
+    use sqlx::{mysql::MySqlConnectOptions, ConnectOptions, Connection};
     use std::str::FromStr;
 
-    let mut conn = MySqlConnectOptions::from_str(connection_url)?
+    let conn = MySqlConnectOptions::from_str(&framework_connection_url(connection_url)?)?
         .connect()
         .await?;
+    Ok(conn.close().await?)
+}
 
-    // Ensure the migrations table exists before we run the migrations
-    conn.ensure_migrations_table().await?;
+/// Open and close a connection, to check that the server accepts them.
+#[cfg(feature = "diesel")]
+pub(crate) async fn ping(connection_url: &str) -> Result<(), Error> {
+    use diesel::Connection;
 
-    let migrator = Migrator::new(migrations_path.as_ref()).await?;
-    migrator.run_direct(&mut conn).await?;
+    let _ = diesel::MysqlConnection::establish(&framework_connection_url(connection_url)?)?;
     Ok(())
 }
 
+/// Check that the server accepts connections with `mysqladmin ping`.
+#[cfg(feature = "cli")]
+pub(crate) async fn ping(connection_url: &str) -> Result<(), Error> {
+    let options = extract_connect_options(connection_url, false)?;
+    let defaults_file = client_option_file(&options)
+        .map(|contents| crate::process::SecretFile::new("cnf", &contents))
+        .transpose()?;
+    let mut cmd = command(
+        &options,
+        std::path::Path::new("mysqladmin"),
+        defaults_file.as_ref(),
+    );
+    let _ = cmd.arg("ping");
+    crate::process::run(&mut cmd)
+        .await
+        .map_err(|error| match error {
+            Error::CommandRunError(output) => Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                output,
+            )),
+            error => error,
+        })
+}
+
+/// Query the server version, e.g. `8.0.35` or `10.11.6-MariaDB-1`.
+#[cfg(feature = "sqlx")]
+async fn server_version(connection_url: &str) -> Result<String, Error> {
+    use sqlx::{mysql::MySqlConnectOptions, ConnectOptions};
+    use std::str::FromStr;
+
+    let mut conn = MySqlConnectOptions::from_str(connection_url)?
+        .connect()
+        .await?;
+    let (version,): (String,) = sqlx::query_as("SELECT VERSION()")
+        .fetch_one(&mut conn)
+        .await?;
+    Ok(version)
+}
+
+/// Query the server version, e.g. `8.0.35` or `10.11.6-MariaDB-1`.
 #[cfg(feature = "diesel")]
-async fn migrate<P: AsRef<std::path::Path>>(
+async fn server_version(connection_url: &str) -> Result<String, Error> {
+    use diesel::{sql_query, sql_types::Text, Connection, QueryableByName, RunQueryDsl};
+
+    #[derive(Debug, QueryableByName)]
+    struct ServerVersion {
+        #[diesel(sql_type = Text)]
+        version: String,
+    }
+
+    let mut conn = diesel::MysqlConnection::establish(connection_url)?;
+    let version: ServerVersion = sql_query("SELECT VERSION() AS version").get_result(&mut conn)?;
+    Ok(version.version)
+}
+
+#[cfg(feature = "sqlx")]
+async fn migrate(
     connection_url: &str,
-    migrations_path: P,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
+) -> Result<(), Error> {
+    use sqlx::{mysql::MySqlConnectOptions, ConnectOptions};
+    use std::str::FromStr;
+
+    let mut conn = MySqlConnectOptions::from_str(connection_url)?
+        .connect()
+        .await?;
+    migrations.run(&mut conn, target).await
+}
+
+#[cfg(feature = "diesel")]
+async fn migrate(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
 ) -> Result<(), Error> {
     use diesel::Connection;
-    use diesel_migrations::{FileBasedMigrations, HarnessWithOutput, MigrationHarness};
     let mut conn = diesel::MysqlConnection::establish(connection_url)?;
-    let migrations = FileBasedMigrations::from_path(migrations_path)?;
-    let _ = HarnessWithOutput::write_to_stdout(&mut conn)
-        .run_pending_migrations(migrations)
-        .map(|_| ());
-    Ok(())
+    migrations.run(&mut conn, target).await
+}
+
+/// Query the server version, e.g. `8.0.35` or `10.11.6-MariaDB-1`.
+#[cfg(feature = "cli")]
+async fn server_version(connection_url: &str) -> Result<String, Error> {
+    Ok(connect(connection_url)
+        .await?
+        .query("SELECT VERSION()")
+        .await?
+        .trim()
+        .to_owned())
+}
+
+#[cfg(feature = "cli")]
+async fn migrate(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
+) -> Result<(), Error> {
+    migrations
+        .run(&mut connect(connection_url).await?, target)
+        .await
 }
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_client_option_file() {
+        let options = super::MySqlConnectOptions::new();
+        assert_eq!(super::client_option_file(&options), None);
+        assert_eq!(
+            super::client_option_file(&options.clone().password(r#"p"a\ss"#)),
+            Some(String::from("[client]\npassword=\"p\\\"a\\\\ss\"\n"))
+        );
+        assert_eq!(
+            super::client_option_file(&options.connect_timeout(std::time::Duration::from_secs(5))),
+            Some(String::from("[client]\nconnect-timeout=5\n"))
+        );
+    }
+
+    #[test]
+    fn test_skip_column_statistics() {
+        let mysql_8 = "mysqldump  Ver 8.0.35 for Linux on x86_64 (MySQL Community Server - GPL)\n";
+        let mariadb =
+            "mysqldump  Ver 10.19 Distrib 10.11.6-MariaDB, for debian-linux-gnu (x86_64)\n";
+        assert!(super::skip_column_statistics(mysql_8, "5.7.44"));
+        assert!(super::skip_column_statistics(mysql_8, "10.11.6-MariaDB-1"));
+        assert!(!super::skip_column_statistics(mysql_8, "8.0.35"));
+        assert!(!super::skip_column_statistics(mysql_8, "8.4.0"));
+        assert!(!super::skip_column_statistics(mariadb, "5.7.44"));
+        assert!(!super::skip_column_statistics(mariadb, "8.0.35"));
+    }
+
+    #[test]
+    fn test_extract_connect_options() -> Result<(), crate::error::Error> {
+        let options = super::extract_connect_options(
+            "mysql://root@localhost/app?charset=utf8mb4&collation=utf8mb4_bin&compression=true&connect_timeout=10&tls-version=TLSv1.2,TLSv1.3&ssl_cert=client.pem",
+            true,
+        )?;
+        assert_eq!(options.charset.as_deref(), Some("utf8mb4"));
+        assert_eq!(options.collation.as_deref(), Some("utf8mb4_bin"));
+        assert!(options.compression);
+        assert_eq!(
+            options.connect_timeout,
+            Some(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(options.tls_version.as_deref(), Some("TLSv1.2,TLSv1.3"));
+        assert_eq!(
+            options.ssl_client_cert,
+            Some(std::path::PathBuf::from("client.pem"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_connect_options_errors() {
+        use crate::error::Error;
+
+        assert!(matches!(
+            super::extract_connect_options("mysql://root:p@ss@[::1/app", false),
+            Err(Error::UriConfiguration(message)) if !message.contains("p@ss")
+        ));
+        assert!(matches!(
+            super::extract_connect_options(
+                "mysql://root@localhost/app?connect_timeout=soon",
+                false
+            ),
+            Err(Error::UriConfiguration(_))
+        ));
+        assert!(matches!(
+            super::extract_connect_options("mysql://root@localhost/app?tls-version=SSLv3", false),
+            Err(Error::UriConfiguration(_))
+        ));
+        assert!(
+            super::extract_connect_options("mysql://root@localhost/app?sslcertt=a.pem", false)
+                .is_ok()
+        );
+        assert!(matches!(
+            super::extract_connect_options("mysql://root@localhost/app?sslcertt=a.pem", true),
+            Err(Error::UriConfiguration(message)) if message.contains("sslcertt")
+        ));
+        assert!(super::extract_connect_options(
+            "mysql://root@localhost/app?statement-cache-capacity=10",
+            true
+        )
+        .is_ok());
+        assert!(matches!(
+            super::extract_connect_options("mysql://root@localhost/app?timezone=%2B00:00", true),
+            Err(Error::UriConfiguration(message)) if message.contains("timezone")
+        ));
+    }
+
+    #[test]
+    fn test_with_default_host() {
+        assert_eq!(
+            super::with_default_host("mysql://root@/app?socket=/tmp/mysql.sock"),
+            "mysql://root@localhost/app?socket=/tmp/mysql.sock"
+        );
+        assert_eq!(
+            super::with_default_host("mysql:///app?socket=/tmp/mysql.sock"),
+            "mysql://localhost/app?socket=/tmp/mysql.sock"
+        );
+        assert_eq!(
+            super::with_default_host("mysql://root:@127.0.0.1:3306/mysql"),
+            "mysql://root:@127.0.0.1:3306/mysql"
+        );
+    }
+
+    #[test]
+    fn test_socket_without_host() -> Result<(), crate::error::Error> {
+        let options =
+            super::extract_connect_options("mysql://root@/app?socket=/tmp/mysql.sock", false)?;
+        assert_eq!(options.host, "localhost");
+        assert_eq!(
+            options.socket,
+            Some(std::path::PathBuf::from("/tmp/mysql.sock"))
+        );
+
+        let url = super::framework_connection_url("mysql://root@/app?unix_socket=/tmp/mysql.sock")?;
+        #[cfg(any(feature = "sqlx", feature = "cli"))]
+        assert_eq!(url, "mysql://root@localhost/app?socket=%2Ftmp%2Fmysql.sock");
+        #[cfg(feature = "diesel")]
+        assert_eq!(
+            url,
+            "mysql://root@localhost/app?unix_socket=%2Ftmp%2Fmysql.sock"
+        );
+        Ok(())
+    }
+
+    /// Runs against the socket-only server from `docker-compose.yml`.
+    #[cfg(all(feature = "sqlx", feature = "mysql"))]
+    #[tokio::test]
+    async fn test_write_structure_sql_socket() -> Result<(), crate::error::Error> {
+        let destination_path = std::env::temp_dir().join("sqlx-mysql-socket-structure.sql");
+        let migrations_path = std::path::PathBuf::from("./fixtures/sqlx/mysql/migrations");
+        let socket =
+            std::path::PathBuf::from("./.data/mysql-8-socket-run/mysqld.sock").canonicalize()?;
+        super::write_structure_sql(
+            &format!("mysql://root@/mysql?socket={}", socket.display()),
+            &crate::migrations::MigrationSource::Directory(migrations_path),
+            &destination_path,
+            &crate::DumpOptions::default(),
+            None,
+            false,
+        )
+        .await?;
+        let contents = std::fs::read_to_string(destination_path)?;
+
+        assert!(contents.contains("CREATE TABLE `sqlx_users`"));
+        Ok(())
+    }
+
+    /// Runs against the TLS server from `docker-compose.yml`, with the certificates of the
+    /// examples.
+    #[tokio::test]
+    async fn test_write_structure_sql_tls() -> Result<(), crate::error::Error> {
+        let certs = std::path::PathBuf::from("./examples/mysql-sqlx/certs");
+        let connection_url = crate::TlsOptions::new()
+            .mode(crate::TlsMode::VerifyCa)
+            .root_cert(certs.join("root-ca.pem"))
+            .client_cert(certs.join("client-cert.pem"))
+            .client_key(certs.join("client-key.pk8.pem"))
+            .apply("mysql://root@127.0.0.1:3307/mysql", crate::Backend::MySql)?;
+        let destination_path =
+            std::env::temp_dir().join(format!("{}-mysql-tls-structure.sql", crate::FRAMEWORK));
+        super::write_structure_sql(
+            &connection_url,
+            &migrations(format!("./fixtures/{}/mysql/migrations", crate::FRAMEWORK)),
+            &destination_path,
+            &crate::DumpOptions::default(),
+            None,
+            false,
+        )
+        .await?;
+        let contents = std::fs::read_to_string(destination_path)?;
+
+        assert!(contents.contains(&format!("CREATE TABLE `{}_users`", crate::FRAMEWORK)));
+        Ok(())
+    }
+
+    /// The migrations of `path`, plain ones with the `cli` feature.
+    fn migrations(path: impl Into<std::path::PathBuf>) -> crate::migrations::MigrationSource {
+        #[cfg(any(feature = "sqlx", feature = "diesel"))]
+        let migrations = crate::migrations::MigrationSource::Directory(path.into());
+        #[cfg(feature = "cli")]
+        let migrations =
+            crate::migrations::MigrationSource::Plain(crate::PlainMigrations::new(path.into()));
+        migrations
+    }
+
     #[cfg(all(feature = "sqlx", feature = "mysql"))]
     #[tokio::test]
     async fn test_write_structure_sql() -> Result<(), crate::error::Error> {
@@ -169,8 +976,11 @@ mod tests {
         let migrations_path = std::path::PathBuf::from("./fixtures/sqlx/mysql/migrations");
         super::write_structure_sql(
             super::DEFAULT_CONNECTION_URL,
-            migrations_path,
+            &crate::migrations::MigrationSource::Directory(migrations_path),
             &destination_path,
+            &crate::DumpOptions::default(),
+            None,
+            false,
         )
         .await?;
         let contents = std::fs::read_to_string(destination_path)?;
@@ -186,8 +996,11 @@ mod tests {
         let migrations_path = std::path::PathBuf::from("./fixtures/diesel/mysql/migrations");
         super::write_structure_sql(
             super::DEFAULT_CONNECTION_URL,
-            migrations_path,
+            &crate::migrations::MigrationSource::Directory(migrations_path),
             &destination_path,
+            &crate::DumpOptions::default(),
+            None,
+            false,
         )
         .await?;
         let contents = std::fs::read_to_string(destination_path)?;
@@ -195,4 +1008,23 @@ mod tests {
         assert!(contents.contains("CREATE TABLE `diesel_users` (\n  `id` varchar(32) NOT NULL,\n  `email` text NOT NULL,\n  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;\n"));
         Ok(())
     }
+
+    #[cfg(all(feature = "cli", feature = "mysql"))]
+    #[tokio::test]
+    async fn test_write_structure_sql() -> Result<(), crate::error::Error> {
+        let destination_path = std::env::temp_dir().join("cli-mysql-structure.sql");
+        super::write_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations("./fixtures/cli/mysql/migrations"),
+            &destination_path,
+            &crate::DumpOptions::default(),
+            None,
+            false,
+        )
+        .await?;
+        let contents = std::fs::read_to_string(destination_path)?;
+
+        assert!(contents.contains("CREATE TABLE `cli_users` (\n  `id` varchar(32) NOT NULL,\n  `email` text NOT NULL,\n  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;\n"));
+        Ok(())
+    }
 }
@@ -8,11 +8,16 @@
 
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
+
+use percent_encoding::utf8_percent_encode;
+
+use crate::URL_COMPONENT;
 
 /// Options for controlling the desired security state of the connection to the MySQL server.
 ///
-/// It is used by the [`ssl_mode`](super::MySqlConnectOptions::ssl_mode) method.
-#[derive(Default, Debug, Clone, Copy)]
+/// It is used by the [`ssl_mode`](MySqlConnectOptions::ssl_mode) method.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MySqlSslMode {
     /// Establish an unencrypted connection.
     Disabled,
@@ -69,7 +74,20 @@ impl FromStr for MySqlSslMode {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Typed options to connect to a MySQL server.
+///
+/// Pass them to [`DatabaseSchemaBuilder::connect_options`](crate::DatabaseSchemaBuilder::connect_options)
+/// instead of assembling a connection URL by hand. They can also be parsed from a
+/// connection URL with [`FromStr`].
+///
+/// ```rust,ignore
+/// let options = MySqlConnectOptions::new()
+///     .host("db.internal")
+///     .username("app")
+///     .password(&secret)
+///     .database("app_production");
+/// ```
+#[derive(Clone)]
 pub struct MySqlConnectOptions {
     pub(crate) host: String,
     pub(crate) port: u16,
@@ -81,6 +99,35 @@ pub struct MySqlConnectOptions {
     pub(crate) ssl_ca: Option<PathBuf>,
     pub(crate) ssl_client_cert: Option<PathBuf>,
     pub(crate) ssl_client_key: Option<PathBuf>,
+    pub(crate) tls_version: Option<String>,
+    pub(crate) charset: Option<String>,
+    pub(crate) collation: Option<String>,
+    pub(crate) compression: bool,
+    pub(crate) connect_timeout: Option<Duration>,
+}
+
+// Written by hand to keep the password out of logs.
+impl std::fmt::Debug for MySqlConnectOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use crate::process::REDACTED;
+        f.debug_struct("MySqlConnectOptions")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("socket", &self.socket)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .field("database", &self.database)
+            .field("ssl_mode", &self.ssl_mode)
+            .field("ssl_ca", &self.ssl_ca)
+            .field("ssl_client_cert", &self.ssl_client_cert)
+            .field("ssl_client_key", &self.ssl_client_key)
+            .field("tls_version", &self.tls_version)
+            .field("charset", &self.charset)
+            .field("collation", &self.collation)
+            .field("compression", &self.compression)
+            .field("connect_timeout", &self.connect_timeout)
+            .finish()
+    }
 }
 
 impl Default for MySqlConnectOptions {
@@ -103,6 +150,11 @@ impl MySqlConnectOptions {
             ssl_ca: None,
             ssl_client_cert: None,
             ssl_client_key: None,
+            tls_version: None,
+            charset: None,
+            collation: None,
+            compression: false,
+            connect_timeout: None,
         }
     }
 
@@ -159,7 +211,7 @@ impl MySqlConnectOptions {
     /// # Example
     ///
     /// ```rust,ignore
-    /// # use sqlx_core::mysql::{MySqlSslMode, MySqlConnectOptions};
+    /// # use database_schema::{MySqlSslMode, MySqlConnectOptions};
     /// let options = MySqlConnectOptions::new()
     ///     .ssl_mode(MySqlSslMode::Required);
     /// ```
@@ -173,7 +225,7 @@ impl MySqlConnectOptions {
     /// # Example
     ///
     /// ```rust,ignore
-    /// # use sqlx_core::mysql::{MySqlSslMode, MySqlConnectOptions};
+    /// # use database_schema::{MySqlSslMode, MySqlConnectOptions};
     /// let options = MySqlConnectOptions::new()
     ///     .ssl_mode(MySqlSslMode::VerifyCa)
     ///     .ssl_ca("path/to/ca.crt");
@@ -188,7 +240,7 @@ impl MySqlConnectOptions {
     /// # Example
     ///
     /// ```rust,ignore
-    /// # use sqlx_core::mysql::{MySqlSslMode, MySqlConnectOptions};
+    /// # use database_schema::{MySqlSslMode, MySqlConnectOptions};
     /// let options = MySqlConnectOptions::new()
     ///     .ssl_mode(MySqlSslMode::VerifyCa)
     ///     .ssl_client_cert("path/to/client.crt");
@@ -203,7 +255,7 @@ impl MySqlConnectOptions {
     /// # Example
     ///
     /// ```rust,ignore
-    /// # use sqlx_core::mysql::{MySqlSslMode, MySqlConnectOptions};
+    /// # use database_schema::{MySqlSslMode, MySqlConnectOptions};
     /// let options = MySqlConnectOptions::new()
     ///     .ssl_mode(MySqlSslMode::VerifyCa)
     ///     .ssl_client_key("path/to/client.key");
@@ -212,4 +264,164 @@ impl MySqlConnectOptions {
         self.ssl_client_key = Some(key.as_ref().to_path_buf());
         self
     }
+
+    /// Sets the TLS protocols mysqldump may use, as a comma separated list (e.g.
+    /// `TLSv1.2,TLSv1.3`).
+    pub fn tls_version(mut self, versions: &str) -> Self {
+        self.tls_version = Some(versions.to_owned());
+        self
+    }
+
+    /// Sets the character set of the connection (e.g. `utf8mb4`).
+    pub fn charset(mut self, charset: &str) -> Self {
+        self.charset = Some(charset.to_owned());
+        self
+    }
+
+    /// Sets the collation of the connection (e.g. `utf8mb4_0900_ai_ci`).
+    ///
+    /// Only the migrations use it: mysqldump writes the collation of every table.
+    pub fn collation(mut self, collation: &str) -> Self {
+        self.collation = Some(collation.to_owned());
+        self
+    }
+
+    /// Sets whether mysqldump compresses the traffic with the server.
+    ///
+    /// By default set to `false`.
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
+    /// Sets how long mysqldump waits for the connection to the server. Only whole seconds
+    /// are used.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Parses a connection URL like [`FromStr`], but rejects unknown query parameters
+    /// instead of ignoring them, to catch typos such as `sslcertt`.
+    pub fn from_url_strict(connection_url: &str) -> Result<Self, crate::error::Error> {
+        super::extract_connect_options(connection_url, true)
+    }
+}
+
+impl FromStr for MySqlConnectOptions {
+    type Err = crate::error::Error;
+
+    fn from_str(connection_url: &str) -> Result<Self, Self::Err> {
+        super::extract_connect_options(connection_url, false)
+    }
+}
+
+impl crate::ConnectionOptions for MySqlConnectOptions {
+    fn connection_url(&self) -> String {
+        let mut url = format!(
+            "mysql://{}",
+            utf8_percent_encode(&self.username, URL_COMPONENT)
+        );
+        if let Some(ref password) = self.password {
+            url.push(':');
+            url.extend(utf8_percent_encode(password, URL_COMPONENT));
+        }
+        url.push_str(&format!("@{}:{}", self.host, self.port));
+        if let Some(ref database) = self.database {
+            url.push('/');
+            url.extend(utf8_percent_encode(database, URL_COMPONENT));
+        }
+
+        // `sqlx` and `diesel` spell the parameters differently, our own parser accepts both.
+        #[cfg(any(feature = "sqlx", feature = "cli"))]
+        let keys = ["socket", "ssl-mode", "ssl-ca", "ssl-cert", "ssl-key"];
+        #[cfg(feature = "diesel")]
+        let keys = ["unix_socket", "ssl_mode", "ssl_ca", "ssl_cert", "ssl_key"];
+
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+        };
+        // Neither framework knows about these, they are only read by our own parser.
+        let extra_keys = [
+            "tls-version",
+            "charset",
+            "collation",
+            "compression",
+            "connect_timeout",
+        ];
+        let query = [
+            path(&self.socket),
+            Some(self.ssl_mode.to_string()),
+            path(&self.ssl_ca),
+            path(&self.ssl_client_cert),
+            path(&self.ssl_client_key),
+            self.tls_version.clone(),
+            self.charset.clone(),
+            self.collation.clone(),
+            self.compression.then(|| String::from("true")),
+            self.connect_timeout
+                .map(|timeout| timeout.as_secs().to_string()),
+        ]
+        .iter()
+        .zip(keys.into_iter().chain(extra_keys))
+        .filter_map(|(value, key)| {
+            value
+                .as_ref()
+                .map(|value| format!("{key}={}", utf8_percent_encode(value, URL_COMPONENT)))
+        })
+        .collect::<Vec<String>>()
+        .join("&");
+        format!("{url}?{query}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MySqlConnectOptions, MySqlSslMode};
+    use crate::ConnectionOptions;
+
+    #[test]
+    fn test_connection_url_roundtrip() -> Result<(), crate::error::Error> {
+        let options = MySqlConnectOptions::new()
+            .host("db.internal")
+            .port(3307)
+            .username("app")
+            .password("p@ss/word?")
+            .database("app")
+            .ssl_mode(MySqlSslMode::VerifyCa)
+            .ssl_ca("/etc/ssl/root ca.pem")
+            .charset("utf8mb4")
+            .compression(true)
+            .connect_timeout(std::time::Duration::from_secs(3));
+        let parsed = MySqlConnectOptions::from_url_strict(&options.connection_url())?;
+        assert_eq!(parsed.host, "db.internal");
+        assert_eq!(parsed.port, 3307);
+        assert_eq!(parsed.username, "app");
+        assert_eq!(parsed.password.as_deref(), Some("p@ss/word?"));
+        assert_eq!(parsed.database.as_deref(), Some("app"));
+        assert_eq!(parsed.ssl_mode, MySqlSslMode::VerifyCa);
+        assert_eq!(
+            parsed.ssl_ca,
+            Some(std::path::PathBuf::from("/etc/ssl/root ca.pem"))
+        );
+        assert_eq!(parsed.charset.as_deref(), Some("utf8mb4"));
+        assert!(parsed.compression);
+        assert_eq!(
+            parsed.connect_timeout,
+            Some(std::time::Duration::from_secs(3))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacts_password() {
+        let options = MySqlConnectOptions::new()
+            .username("app")
+            .password("s3cr3t");
+        let debug = format!("{options:?}");
+        assert!(debug.contains("username: \"app\""));
+        assert!(debug.contains("password: Some(\"*****\")"));
+        assert!(!debug.contains("s3cr3t"));
+    }
 }
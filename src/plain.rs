@@ -0,0 +1,357 @@
+//! Migrations as plain, ordered `.sql` files, for projects that don't use the migrations of
+//! `sqlx` or `diesel`: hand-written `001_create_users.sql` files, refinery's
+//! `V1__create_users.sql`...
+//!
+//! The files are run with the connection of the enabled framework, or the client of the
+//! database with the `cli` feature, and recorded in a table of their own, so that only the
+//! pending ones run against an existing database. The schema is then dumped exactly like
+//! with the other migration sources.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::Error,
+    migrations::{version_order, UpMigration},
+};
+
+/// Table recording the applied migrations, unless another one is set.
+pub(crate) const DEFAULT_TABLE: &str = "_database_schema_migrations";
+
+/// A directory of plain SQL migrations.
+///
+/// Every `.sql` file directly in the directory is a migration, except `.down.sql` ones.
+/// Its name must start with a version, optionally prefixed with `V`, followed by `_` or
+/// `.`: `001_create_users.sql`, `20240101120000_create_users.sql`, `V1__create_users.sql`.
+/// Migrations are applied in the numeric order of their version, so `10_` runs after `9_`
+/// even without zero padding.
+///
+/// ```rust,ignore
+/// DatabaseSchemaBuilder::new()
+///     .plain_migrations(PlainMigrations::new("db/sql").table("schema_history"))
+///     .build()
+///     .dump()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainMigrations {
+    pub(crate) dir: PathBuf,
+    pub(crate) table: Option<String>,
+}
+
+impl PlainMigrations {
+    /// Read the migrations from `dir`, recording them in the `_database_schema_migrations`
+    /// table.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            table: Some(String::from(DEFAULT_TABLE)),
+        }
+    }
+
+    /// Record the applied migrations in `table` instead. It may be qualified with a
+    /// schema (`meta.migrations`).
+    pub fn table<S: Into<String>>(mut self, table: S) -> Self {
+        self.table = Some(table.into());
+        self
+    }
+
+    /// Don't record the applied migrations at all, for throwaway databases: every
+    /// migration is run on every dump, and no migrations table ends up in the dump.
+    pub fn untracked(mut self) -> Self {
+        self.table = None;
+        self
+    }
+
+    /// Read the migrations, in the order they are applied.
+    pub(crate) fn migrations(&self) -> Result<Vec<UpMigration>, Error> {
+        let mut migrations = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !entry.file_type()?.is_file()
+                || !name.ends_with(".sql")
+                || name.ends_with(".down.sql")
+            {
+                continue;
+            }
+            let version = version_from_name(&name).ok_or_else(|| {
+                Error::MigrationSourceError(format!(
+                    "`{name}` doesn't start with a version, such as `001_{name}`"
+                ))
+            })?;
+            migrations.push(UpMigration {
+                version,
+                name,
+                sql: std::fs::read_to_string(entry.path())?,
+            });
+        }
+        migrations.sort_by(|a, b| {
+            version_order(&a.version)
+                .cmp(&version_order(&b.version))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        if let Some(pair) = migrations
+            .windows(2)
+            .find(|pair| version_order(&pair[0].version) == version_order(&pair[1].version))
+        {
+            return Err(Error::MigrationSourceError(format!(
+                "`{}` and `{}` have the same version",
+                pair[0].name, pair[1].name
+            )));
+        }
+        Ok(migrations)
+    }
+
+    /// The migrations table, checked to be a plain (possibly qualified) identifier since
+    /// it ends up in the queries as is.
+    pub(crate) fn checked_table(&self) -> Result<Option<&str>, Error> {
+        match self.table.as_deref() {
+            Some(table)
+                if !table.is_empty()
+                    && table
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') =>
+            {
+                Ok(Some(table))
+            }
+            Some(table) => Err(Error::MigrationSourceError(format!(
+                "invalid migrations table name `{table}`"
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The version at the start of a migration file name: `001` for `001_create_users.sql`,
+/// `1` for `V1__create_users.sql`.
+pub(crate) fn version_from_name(name: &str) -> Option<String> {
+    let name = name.strip_prefix('V').unwrap_or(name);
+    let (version, rest) = name.split_at(name.find(|c: char| !c.is_ascii_digit())?);
+    (!version.is_empty() && (rest.starts_with('_') || rest.starts_with('.')))
+        .then(|| version.to_owned())
+}
+
+/// Runs plain SQL on a connection of the enabled framework, or through the client of the
+/// database with the `cli` feature.
+pub(crate) trait Execute {
+    /// Run `sql`, which may contain several statements.
+    async fn execute_sql(&mut self, sql: &str) -> Result<(), Error>;
+
+    /// Run `query`, returning its text `version` column.
+    async fn fetch_versions(&mut self, query: &str) -> Result<Vec<String>, Error>;
+}
+
+#[cfg(feature = "sqlx")]
+macro_rules! impl_execute {
+    ($connection:ty) => {
+        impl Execute for $connection {
+            async fn execute_sql(&mut self, sql: &str) -> Result<(), Error> {
+                let _ = sqlx::Executor::execute(&mut *self, sql).await?;
+                Ok(())
+            }
+
+            async fn fetch_versions(&mut self, query: &str) -> Result<Vec<String>, Error> {
+                Ok(sqlx::query_scalar::<_, String>(query)
+                    .fetch_all(&mut *self)
+                    .await?)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "diesel")]
+#[derive(diesel::QueryableByName)]
+struct Version {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    version: String,
+}
+
+#[cfg(feature = "diesel")]
+macro_rules! impl_execute {
+    ($connection:ty) => {
+        impl Execute for $connection {
+            async fn execute_sql(&mut self, sql: &str) -> Result<(), Error> {
+                Ok(diesel::connection::SimpleConnection::batch_execute(
+                    self, sql,
+                )?)
+            }
+
+            async fn fetch_versions(&mut self, query: &str) -> Result<Vec<String>, Error> {
+                Ok(
+                    diesel::RunQueryDsl::load::<Version>(diesel::sql_query(query), self)?
+                        .into_iter()
+                        .map(|row| row.version)
+                        .collect(),
+                )
+            }
+        }
+    };
+}
+
+#[cfg(all(feature = "sqlx", feature = "sqlite"))]
+impl_execute!(sqlx::sqlite::SqliteConnection);
+#[cfg(all(feature = "sqlx", feature = "postgres"))]
+impl_execute!(sqlx::postgres::PgConnection);
+#[cfg(all(feature = "sqlx", feature = "mysql"))]
+impl_execute!(sqlx::mysql::MySqlConnection);
+#[cfg(all(feature = "diesel", feature = "sqlite"))]
+impl_execute!(diesel::SqliteConnection);
+#[cfg(all(feature = "diesel", feature = "postgres"))]
+impl_execute!(diesel::PgConnection);
+#[cfg(all(feature = "diesel", feature = "mysql"))]
+impl_execute!(diesel::MysqlConnection);
+
+/// The migrations that aren't recorded as applied yet, creating the migrations table if
+/// needed. Without a migrations table, that's all of them.
+pub(crate) async fn pending<C: Execute>(
+    conn: &mut C,
+    migrations: &PlainMigrations,
+) -> Result<Vec<UpMigration>, Error> {
+    let all = migrations.migrations()?;
+    let Some(table) = migrations.checked_table()? else {
+        return Ok(all);
+    };
+    conn.execute_sql(&format!(
+        "CREATE TABLE IF NOT EXISTS {table} (
+    version VARCHAR(255) PRIMARY KEY NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)"
+    ))
+    .await?;
+    let applied = conn
+        .fetch_versions(&format!("SELECT version FROM {table}"))
+        .await?;
+    Ok(all
+        .into_iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+/// Run `migration` and record it as applied.
+///
+/// The migration isn't wrapped in a transaction, so that statements like `CREATE INDEX
+/// CONCURRENTLY` can be used: a failing migration may be left half applied.
+pub(crate) async fn apply<C: Execute>(
+    conn: &mut C,
+    migrations: &PlainMigrations,
+    migration: &UpMigration,
+) -> Result<(), Error> {
+    tracing::debug!(
+        version = migration.version,
+        name = migration.name,
+        "Applying plain migration"
+    );
+    // Some drivers reject empty queries, such as a file holding only comments.
+    if !migration.sql.trim().is_empty() {
+        conn.execute_sql(&migration.sql).await.map_err(|error| {
+            Error::PlainMigrationFailed(migration.name.clone(), error.to_string())
+        })?;
+    }
+    if let Some(table) = migrations.checked_table()? {
+        insert(conn, table, migration).await?;
+    }
+    Ok(())
+}
+
+/// Record `migration` as applied in `table`.
+async fn insert<C: Execute>(
+    conn: &mut C,
+    table: &str,
+    migration: &UpMigration,
+) -> Result<(), Error> {
+    conn.execute_sql(&format!(
+        "INSERT INTO {table} (version, name) VALUES ('{}', '{}')",
+        migration.version,
+        migration.name.replace('\'', "''")
+    ))
+    .await
+}
+
+/// Record the pending migrations as applied without running them, only up to the
+/// `target` version (included) if set. Returns how many were recorded.
+pub(crate) async fn record<C: Execute>(
+    conn: &mut C,
+    migrations: &PlainMigrations,
+    target: Option<&str>,
+) -> Result<usize, Error> {
+    let Some(table) = migrations.checked_table()? else {
+        return Ok(0);
+    };
+    let pending = pending(conn, migrations).await?;
+    let pending = pending
+        .iter()
+        .filter(|migration| {
+            target.is_none_or(|target| version_order(&migration.version) <= version_order(target))
+        })
+        .collect::<Vec<&UpMigration>>();
+    for migration in &pending {
+        insert(conn, table, migration).await?;
+    }
+    Ok(pending.len())
+}
+
+/// Run the pending migrations on `conn`, only up to the `target` version (included) if
+/// set.
+pub(crate) async fn migrate<C: Execute>(
+    conn: &mut C,
+    migrations: &PlainMigrations,
+    target: Option<&str>,
+) -> Result<(), Error> {
+    for migration in pending(conn, migrations).await?.iter().filter(|migration| {
+        target.is_none_or(|target| version_order(&migration.version) <= version_order(target))
+    }) {
+        apply(conn, migrations, migration).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlainMigrations;
+
+    #[test]
+    fn test_version_from_name() {
+        assert_eq!(
+            super::version_from_name("001_create_users.sql").as_deref(),
+            Some("001")
+        );
+        assert_eq!(
+            super::version_from_name("V12__create_users.sql").as_deref(),
+            Some("12")
+        );
+        assert_eq!(super::version_from_name("3.sql").as_deref(), Some("3"));
+        assert_eq!(super::version_from_name("create_users.sql"), None);
+        assert_eq!(super::version_from_name("1a_create_users.sql"), None);
+    }
+
+    #[test]
+    fn test_migrations() -> Result<(), crate::error::Error> {
+        let migrations = PlainMigrations::new("./fixtures/plain/sqlite").migrations()?;
+        assert_eq!(
+            migrations
+                .iter()
+                .map(|migration| migration.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "1_create_users.sql",
+                "2_create_posts.sql",
+                "10_add_users_name.sql"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_table() {
+        let migrations = PlainMigrations::new("./fixtures/plain/sqlite").table("users; --");
+        assert!(migrations.checked_table().is_err());
+        assert_eq!(
+            PlainMigrations::new("./fixtures/plain/sqlite")
+                .untracked()
+                .checked_table()
+                .ok(),
+            Some(None)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Handling of extensions in `pg_dump` output.
+//!
+//! Migrations that run `CREATE EXTENSION` make `pg_dump` emit the extension itself, a
+//! `COMMENT ON EXTENSION` line and, depending on the extension, objects it owns. The
+//! comments can only be loaded by the owner of the extension (usually a superuser) and
+//! the objects vary between server installations, so both can be stripped from the dump.
+
+use crate::error::Error;
+
+/// Controls what happens to extensions in the dump.
+///
+/// Without these options the output of `pg_dump` is written untouched.
+///
+/// ```rust,ignore
+/// DatabaseSchemaBuilder::new()
+///     .postgres_extensions(ExtensionOptions::new().keep_create_extension(false))
+///     .build()
+///     .dump()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtensionOptions {
+    pub(crate) keep_create_extension: bool,
+    pub(crate) drop_extension_comments: bool,
+    pub(crate) exclude_extension_objects: bool,
+}
+
+impl Default for ExtensionOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExtensionOptions {
+    /// Creates the default set of options: keep `CREATE EXTENSION IF NOT EXISTS`, but
+    /// drop extension comments and objects owned by extensions.
+    pub fn new() -> Self {
+        Self {
+            keep_create_extension: true,
+            drop_extension_comments: true,
+            exclude_extension_objects: true,
+        }
+    }
+
+    /// Whether to keep the `CREATE EXTENSION IF NOT EXISTS` statements.
+    ///
+    /// Dropping them also drops the comments on those extensions.
+    pub fn keep_create_extension(mut self, keep: bool) -> Self {
+        self.keep_create_extension = keep;
+        self
+    }
+
+    /// Whether to drop `COMMENT ON EXTENSION` statements.
+    pub fn drop_extension_comments(mut self, drop: bool) -> Self {
+        self.drop_extension_comments = drop;
+        self
+    }
+
+    /// Whether to drop objects (tables, functions, types, schemas...) that belong to an
+    /// extension.
+    ///
+    /// This queries `pg_depend` for the objects each extension owns.
+    pub fn exclude_extension_objects(mut self, exclude: bool) -> Self {
+        self.exclude_extension_objects = exclude;
+        self
+    }
+}
+
+/// Objects owned by an extension, as `(schema, name)`. Schemas themselves use `-` as
+/// their schema, which is what `pg_dump` prints for them.
+const EXTENSION_OBJECTS_QUERY: &str = "SELECT n.nspname::text AS schema_name, c.relname::text AS object_name
+FROM pg_catalog.pg_depend d
+JOIN pg_catalog.pg_class c ON d.classid = 'pg_catalog.pg_class'::regclass AND d.objid = c.oid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+WHERE d.deptype = 'e'
+UNION ALL
+SELECT n.nspname::text, p.proname::text
+FROM pg_catalog.pg_depend d
+JOIN pg_catalog.pg_proc p ON d.classid = 'pg_catalog.pg_proc'::regclass AND d.objid = p.oid
+JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace
+WHERE d.deptype = 'e'
+UNION ALL
+SELECT n.nspname::text, t.typname::text
+FROM pg_catalog.pg_depend d
+JOIN pg_catalog.pg_type t ON d.classid = 'pg_catalog.pg_type'::regclass AND d.objid = t.oid
+JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace
+WHERE d.deptype = 'e'
+UNION ALL
+SELECT '-', n.nspname::text
+FROM pg_catalog.pg_depend d
+JOIN pg_catalog.pg_namespace n ON d.classid = 'pg_catalog.pg_namespace'::regclass AND d.objid = n.oid
+WHERE d.deptype = 'e'";
+
+/// Rewrite the dump in `destination_path` according to `options`.
+pub(crate) async fn apply<P: AsRef<std::path::Path>>(
+    options: &ExtensionOptions,
+    connection_url: &str,
+    destination_path: P,
+) -> Result<(), Error> {
+    let owned = if options.exclude_extension_objects {
+        fetch_extension_objects(&super::framework_connection_url(connection_url)?).await?
+    } else {
+        Vec::new()
+    };
+    let contents = std::fs::read_to_string(&destination_path)?;
+    Ok(std::fs::write(
+        destination_path,
+        filter(&contents, options, &owned),
+    )?)
+}
+
+#[cfg(feature = "sqlx")]
+async fn fetch_extension_objects(connection_url: &str) -> Result<Vec<(String, String)>, Error> {
+    use sqlx::{postgres::PgConnectOptions, ConnectOptions};
+    use std::str::FromStr;
+
+    let mut conn = PgConnectOptions::from_str(connection_url)?
+        .connect()
+        .await?;
+    Ok(sqlx::query_as(EXTENSION_OBJECTS_QUERY)
+        .fetch_all(&mut conn)
+        .await?)
+}
+
+#[cfg(feature = "diesel")]
+async fn fetch_extension_objects(connection_url: &str) -> Result<Vec<(String, String)>, Error> {
+    use diesel::{sql_query, sql_types::Text, Connection, QueryableByName, RunQueryDsl};
+
+    #[derive(Debug, QueryableByName)]
+    struct ExtensionObject {
+        #[diesel(sql_type = Text)]
+        schema_name: String,
+        #[diesel(sql_type = Text)]
+        object_name: String,
+    }
+
+    let mut conn = diesel::PgConnection::establish(connection_url)?;
+    let objects: Vec<ExtensionObject> = sql_query(EXTENSION_OBJECTS_QUERY).load(&mut conn)?;
+    Ok(objects
+        .into_iter()
+        .map(|o| (o.schema_name, o.object_name))
+        .collect())
+}
+
+#[cfg(feature = "cli")]
+async fn fetch_extension_objects(connection_url: &str) -> Result<Vec<(String, String)>, Error> {
+    Ok(super::connect(connection_url)
+        .await?
+        .rows(EXTENSION_OBJECTS_QUERY)
+        .await?
+        .into_iter()
+        .filter_map(|row| match <[String; 2]>::try_from(row) {
+            Ok([schema_name, object_name]) => Some((schema_name, object_name)),
+            Err(_) => None,
+        })
+        .collect())
+}
+
+/// A `pg_dump` TOC entry header, e.g. `-- Name: users; Type: TABLE; Schema: public; Owner: -`
+#[derive(Debug)]
+struct EntryHeader<'a> {
+    name: &'a str,
+    r#type: &'a str,
+    schema: &'a str,
+}
+
+impl<'a> EntryHeader<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let rest = line.strip_prefix("-- Name: ")?;
+        let mut parts = rest.split("; ");
+        let name = parts.next()?;
+        let r#type = parts.next()?.strip_prefix("Type: ")?;
+        let schema = parts.next()?.strip_prefix("Schema: ")?;
+        Some(Self {
+            name,
+            r#type,
+            schema,
+        })
+    }
+
+    /// Name of the object the entry is about, without arguments and, for comments and
+    /// privileges, without the leading object kind (`FUNCTION digest(...)` -> `digest`).
+    fn object_name(&self) -> &'a str {
+        let name = match self.r#type {
+            "COMMENT" | "ACL" => self.name.split_once(' ').map_or(self.name, |(_, n)| n),
+            _ => self.name,
+        };
+        name.split('(').next().unwrap_or(name).trim()
+    }
+
+    fn is_extension_comment(&self) -> bool {
+        self.r#type == "COMMENT" && self.name.starts_with("EXTENSION ")
+    }
+
+    fn should_drop(&self, options: &ExtensionOptions, owned: &[(String, String)]) -> bool {
+        if self.r#type == "EXTENSION" {
+            return !options.keep_create_extension;
+        }
+        if self.is_extension_comment() {
+            return options.drop_extension_comments || !options.keep_create_extension;
+        }
+        let object_name = self.object_name();
+        owned
+            .iter()
+            .any(|(schema, name)| schema == self.schema && name == object_name)
+    }
+}
+
+/// Drop the entries selected by `options` from a `pg_dump` output.
+///
+/// Entries start with a three line comment header. Session settings (`SET ...`) that
+/// `pg_dump` places between entries are kept even when the entry before them goes.
+fn filter(contents: &str, options: &ExtensionOptions, owned: &[(String, String)]) -> String {
+    let lines = contents.split_inclusive('\n').collect::<Vec<&str>>();
+    let mut output = String::with_capacity(contents.len());
+    let mut dropping = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let header = (lines[i] == "--\n")
+            .then(|| lines.get(i + 1))
+            .flatten()
+            .and_then(|line| EntryHeader::parse(line.trim_end()))
+            .filter(|_| lines.get(i + 2) == Some(&"--\n"));
+        if let Some(header) = header {
+            dropping = header.should_drop(options, owned);
+            if dropping {
+                i += 3;
+                continue;
+            }
+        } else if dropping && lines[i].starts_with("-- PostgreSQL database dump complete") {
+            // The footer is framed like an entry header; put back the opening `--`.
+            output.push_str("--\n");
+            dropping = false;
+        }
+
+        let keep_anyway = lines[i].starts_with("SET ")
+            || lines[i].starts_with("SELECT pg_catalog.set_config(")
+            || lines[i].starts_with('\\');
+        if !dropping || keep_anyway {
+            output.push_str(lines[i]);
+        }
+        i += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExtensionOptions;
+
+    const DUMP: &str = "SET row_security = off;
+
+--
+-- Name: citext; Type: EXTENSION; Schema: -; Owner: -
+--
+
+CREATE EXTENSION IF NOT EXISTS citext WITH SCHEMA public;
+
+
+--
+-- Name: EXTENSION citext; Type: COMMENT; Schema: -; Owner: -
+--
+
+COMMENT ON EXTENSION citext IS 'data type for case-insensitive character strings';
+
+
+--
+-- Name: citext_eq(public.citext, public.citext); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.citext_eq(public.citext, public.citext) RETURNS boolean
+    LANGUAGE c IMMUTABLE STRICT PARALLEL SAFE
+    AS '$libdir/citext', 'citext_eq';
+
+
+SET default_tablespace = '';
+
+--
+-- Name: users; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.users (
+    email public.citext
+);
+
+
+--
+-- PostgreSQL database dump complete
+--
+";
+
+    fn owned() -> Vec<(String, String)> {
+        vec![(String::from("public"), String::from("citext_eq"))]
+    }
+
+    #[test]
+    fn test_filter_defaults() {
+        let filtered = super::filter(DUMP, &ExtensionOptions::new(), &owned());
+        assert!(filtered.contains("CREATE EXTENSION IF NOT EXISTS citext WITH SCHEMA public;"));
+        assert!(!filtered.contains("COMMENT ON EXTENSION"));
+        assert!(!filtered.contains("citext_eq"));
+        assert!(filtered.contains("SET default_tablespace = '';"));
+        assert!(filtered.contains("CREATE TABLE public.users"));
+        assert!(filtered.ends_with("--\n-- PostgreSQL database dump complete\n--\n"));
+    }
+
+    #[test]
+    fn test_filter_drop_create_extension() {
+        let options = ExtensionOptions::new()
+            .keep_create_extension(false)
+            .drop_extension_comments(false)
+            .exclude_extension_objects(false);
+        let filtered = super::filter(DUMP, &options, &[]);
+        assert!(!filtered.contains("CREATE EXTENSION"));
+        assert!(!filtered.contains("COMMENT ON EXTENSION"));
+        assert!(filtered.contains("citext_eq"));
+    }
+
+    #[test]
+    fn test_filter_keep_everything() {
+        let options = ExtensionOptions::new()
+            .drop_extension_comments(false)
+            .exclude_extension_objects(false);
+        assert_eq!(super::filter(DUMP, &options, &[]), DUMP);
+    }
+}
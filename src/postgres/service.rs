@@ -0,0 +1,189 @@
+//! Connection service files (`~/.pg_service.conf`).
+//!
+//! libpq, and so `diesel` and `pg_dump`, look up `service=name` parameters on their own.
+//! `sqlx` ignores them, so for it we resolve the service into plain connection
+//! parameters, following the same rules: the service (or `PGSERVICE`) is read from
+//! `PGSERVICEFILE` or `~/.pg_service.conf`, then from `$PGSYSCONFDIR/pg_service.conf`, and
+//! parameters set in the URL win over the ones from the service.
+
+use std::path::{Path, PathBuf};
+
+use percent_encoding::utf8_percent_encode;
+
+use crate::error::Error;
+
+/// Service parameters `sqlx` understands as query parameters.
+const PARAMETERS: [&str; 12] = [
+    "host",
+    "hostaddr",
+    "port",
+    "dbname",
+    "user",
+    "password",
+    "sslmode",
+    "sslrootcert",
+    "sslcert",
+    "sslkey",
+    "application_name",
+    "options",
+];
+
+/// Replace the `service` of `connection_url`, or the `PGSERVICE` environment variable, by
+/// the parameters it defines.
+pub(crate) fn resolve(connection_url: &str) -> Result<String, Error> {
+    resolve_from(connection_url, &service_files())
+}
+
+/// Same as [`resolve`], looking for the service in `files` only.
+pub(crate) fn resolve_from<P: AsRef<Path>>(
+    connection_url: &str,
+    files: &[P],
+) -> Result<String, Error> {
+    let url =
+        url::Url::parse(connection_url).map_err(|e| Error::UriConfiguration(e.to_string()))?;
+    let service = url
+        .query_pairs()
+        .find(|(key, _)| key == "service")
+        .map(|(_, value)| value.into_owned())
+        .or_else(|| std::env::var("PGSERVICE").ok());
+    let Some(service) = service else {
+        return Ok(connection_url.to_owned());
+    };
+
+    for path in files.iter().map(AsRef::as_ref) {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        if let Some(parameters) = parse(&contents, &service) {
+            tracing::debug!(service, path = %path.display(), "Found connection service");
+            return Ok(apply(url, &parameters));
+        }
+    }
+    Err(Error::UriConfiguration(format!(
+        "definition of service \"{service}\" not found"
+    )))
+}
+
+/// Service files in lookup order.
+fn service_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    match std::env::var_os("PGSERVICEFILE") {
+        Some(path) => files.push(PathBuf::from(path)),
+        None => {
+            if let Some(home) = std::env::var_os("HOME") {
+                files.push(PathBuf::from(home).join(".pg_service.conf"));
+            }
+        }
+    }
+    if let Some(dir) = std::env::var_os("PGSYSCONFDIR") {
+        files.push(PathBuf::from(dir).join("pg_service.conf"));
+    }
+    files
+}
+
+/// Parameters of the `[service]` section of a service file, if there is one.
+fn parse(contents: &str, service: &str) -> Option<Vec<(String, String)>> {
+    let mut parameters = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if parameters.is_some() {
+                break;
+            }
+            if section == service {
+                parameters = Some(Vec::new());
+            }
+            continue;
+        }
+        if let (Some(parameters), Some((key, value))) = (parameters.as_mut(), line.split_once('='))
+        {
+            parameters.push((key.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+    parameters
+}
+
+/// Add the service `parameters` that aren't set in `url` already, and drop `service`.
+fn apply(mut url: url::Url, parameters: &[(String, String)]) -> String {
+    let mut query = url
+        .query_pairs()
+        .filter(|(key, _)| key != "service")
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<(String, String)>>();
+    let is_set = |key: &str, query: &[(String, String)]| {
+        query.iter().any(|(k, _)| k == key)
+            || match key {
+                "host" | "hostaddr" => url.host_str().is_some_and(|host| !host.is_empty()),
+                "port" => url.port().is_some(),
+                "user" => !url.username().is_empty(),
+                "password" => url.password().is_some(),
+                "dbname" => !url.path().trim_start_matches('/').is_empty(),
+                _ => false,
+            }
+    };
+    for (key, value) in parameters {
+        if !PARAMETERS.contains(&key.as_str()) {
+            tracing::warn!(%key, "Ignoring unsupported connection service parameter");
+        } else if !is_set(key, &query) {
+            query.push((key.clone(), value.clone()));
+        }
+    }
+
+    // libpq only decodes `%XX` escapes in parameters (not `+`), so we can't use
+    // `query_pairs_mut` here.
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{key}={}", utf8_percent_encode(value, crate::URL_COMPONENT)))
+        .collect::<Vec<String>>();
+    url.set_query((!query.is_empty()).then(|| query.join("&")).as_deref());
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    const SERVICE_FILE: &str = "
+# Development databases
+[app_dev]
+host=db.internal
+port = 5433
+dbname=app development
+user=app
+sslmode=require
+connect_timeout=10
+
+[other]
+host=elsewhere
+";
+
+    #[test]
+    fn test_parse() {
+        let parameters = super::parse(SERVICE_FILE, "app_dev").unwrap_or_default();
+        assert_eq!(parameters.len(), 6);
+        assert_eq!(parameters[1], (String::from("port"), String::from("5433")));
+        assert_eq!(super::parse(SERVICE_FILE, "missing"), None);
+    }
+
+    #[test]
+    fn test_apply() -> Result<(), url::ParseError> {
+        let parameters = super::parse(SERVICE_FILE, "app_dev").unwrap_or_default();
+        assert_eq!(
+            super::apply(
+                url::Url::parse("postgresql:///?service=app_dev")?,
+                &parameters
+            ),
+            "postgresql:///?host=db.internal&port=5433&dbname=app%20development&user=app&sslmode=require"
+        );
+        assert_eq!(
+            super::apply(
+                url::Url::parse(
+                    "postgresql://root@localhost/postgres?service=app_dev&sslmode=disable"
+                )?,
+                &parameters
+            ),
+            "postgresql://root@localhost/postgres?sslmode=disable&port=5433"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+//! Waiting for the database server to accept connections.
+//!
+//! In docker-compose and CI the server is often still starting when `dump()` runs. With
+//! [`RetryOptions`] set on the builder, we try to connect until it succeeds, backing off
+//! between attempts, before running the migrations.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::{error::Error, Backend};
+
+/// How long, how often and on which errors to retry connecting to the database.
+///
+/// ```rust,ignore
+/// DatabaseSchemaBuilder::new()
+///     .retry(
+///         RetryOptions::new()
+///             .max_wait(Duration::from_secs(60))
+///             .backoff(Duration::from_millis(500), Duration::from_secs(5)),
+///     )
+///     .build()
+///     .dump()
+///     .await?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RetryOptions {
+    pub(crate) max_wait: Duration,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) retryable: fn(&Error) -> bool,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryOptions {
+    /// Creates the default set of options: retry connection errors for up to 30 seconds,
+    /// starting with a 250ms pause and doubling it up to 5 seconds.
+    pub fn new() -> Self {
+        Self {
+            max_wait: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            retryable: is_connection_error,
+        }
+    }
+
+    /// Sets for how long to keep trying. No attempt starts after this much time has passed
+    /// since the first one.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Sets the pause after the first failed attempt, and the longest pause: pauses
+    /// double after every failed attempt until they reach `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Sets which errors are worth retrying. Any other error is returned straight away.
+    ///
+    /// By default set to [`is_connection_error`].
+    pub fn retryable(mut self, retryable: fn(&Error) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Run `operation` until it succeeds, fails with an error that isn't retryable, or
+    /// `max_wait` is over.
+    ///
+    /// The pauses don't block the executor, see [`sleep`].
+    pub(crate) async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let start = Instant::now();
+        let mut backoff = self.initial_backoff;
+        let mut attempts = Vec::new();
+        loop {
+            let attempt = attempts.len() + 1;
+            let error = match operation().await {
+                Ok(value) => {
+                    tracing::debug!(attempt, elapsed = ?start.elapsed(), "Connected to the database");
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
+
+            let retryable = (self.retryable)(&error);
+            tracing::warn!(attempt, elapsed = ?start.elapsed(), %error, retryable, "Unable to connect to the database");
+            if !retryable && attempts.is_empty() {
+                return Err(error);
+            }
+            attempts.push(format!(
+                "attempt {attempt} after {:.1?}: {error}",
+                start.elapsed()
+            ));
+            if !retryable || start.elapsed() + backoff > self.max_wait {
+                return Err(Error::ConnectionRetriesExhausted(attempts));
+            }
+
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+/// Pause for `duration` without blocking the executor.
+///
+/// The crate works with any async runtime, or none with `diesel`, so the pause is timed
+/// by a helper thread that wakes the task up, rather than by the timer of a runtime.
+fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        duration,
+        state: None,
+    }
+}
+
+/// Future returned by [`sleep`].
+struct Sleep {
+    duration: Duration,
+    /// Shared with the helper thread once the future is first polled.
+    state: Option<Arc<Mutex<SleepState>>>,
+}
+
+#[derive(Default)]
+struct SleepState {
+    /// Whether the helper thread is done sleeping.
+    done: bool,
+    /// The task to wake up when it is.
+    waker: Option<Waker>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let duration = self.duration;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(Mutex::new(SleepState::default()));
+            let thread_state = Arc::clone(&state);
+            let _ = std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+                state.done = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+            state
+        });
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.done {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Whether `error` looks like the server isn't accepting connections yet: the connection
+/// was refused or reset, or the server is still starting up. Other errors, such as a
+/// missing certificate or service file or a bad password, aren't retried.
+///
+/// This is the default [`retryable`](RetryOptions::retryable) predicate.
+pub fn is_connection_error(error: &Error) -> bool {
+    match error {
+        Error::IOError(error) => is_connection_io_error(error),
+        #[cfg(feature = "sqlx")]
+        Error::DBError(error) => match error {
+            sqlx::Error::Io(error) => is_connection_io_error(error),
+            sqlx::Error::PoolTimedOut => true,
+            // `57P03`: the database system is starting up (postgres), `08xxx`: connection
+            // exceptions
+            sqlx::Error::Database(error) => error
+                .code()
+                .is_some_and(|code| code == "57P03" || code.starts_with("08")),
+            _ => false,
+        },
+        // `diesel` only has the message of the client library, which also reports
+        // authentication failures as bad connections.
+        #[cfg(feature = "diesel")]
+        Error::DBConnectionError(diesel::ConnectionError::BadConnection(message)) => {
+            DIESEL_CONNECTION_ERRORS
+                .iter()
+                .any(|error| message.contains(error))
+        }
+        _ => false,
+    }
+}
+
+fn is_connection_io_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::NotConnected
+            | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Messages of `libpq` and `libmysqlclient` for servers that refuse connections or are
+/// still starting up.
+#[cfg(feature = "diesel")]
+const DIESEL_CONNECTION_ERRORS: [&str; 8] = [
+    "Connection refused",
+    "Connection reset",
+    "Connection timed out",
+    "the database system is starting up",
+    "the database system is not yet accepting connections",
+    "server closed the connection unexpectedly",
+    "Can't connect to",
+    "Lost connection to MySQL server",
+];
+
+/// Wait until the server behind `connection_url` accepts connections.
+pub(crate) async fn wait_for_database(
+    options: &RetryOptions,
+    backend: Backend,
+    connection_url: &str,
+) -> Result<(), Error> {
+    match backend {
+        #[cfg(feature = "mysql")]
+        Backend::MySql => options.run(|| crate::mysql::ping(connection_url)).await,
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => options.run(|| crate::postgres::ping(connection_url)).await,
+        // Opening a SQLite database doesn't depend on a server.
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::RetryOptions;
+    use crate::Error;
+
+    fn refused() -> Error {
+        Error::IOError(std::io::ErrorKind::ConnectionRefused.into())
+    }
+
+    #[tokio::test]
+    async fn test_run_until_success() -> Result<(), Error> {
+        let calls = Cell::new(0);
+        let options =
+            RetryOptions::new().backoff(Duration::from_millis(1), Duration::from_millis(2));
+        let value = options
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err(refused())
+                } else {
+                    Ok(calls.get())
+                }
+            })
+            .await?;
+        assert_eq!(value, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_attempts() {
+        let options = RetryOptions::new()
+            .max_wait(Duration::from_millis(20))
+            .backoff(Duration::from_millis(5), Duration::from_millis(5));
+        let result: Result<(), Error> = options.run(|| async { Err(refused()) }).await;
+        assert!(matches!(
+            result,
+            Err(Error::ConnectionRetriesExhausted(attempts))
+                if attempts.len() > 1 && attempts[0].starts_with("attempt 1 after ")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_not_retryable() {
+        let calls = Cell::new(0);
+        let result: Result<(), Error> = RetryOptions::new()
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                Err(Error::UriConfiguration(String::from("bad URL")))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::UriConfiguration(_))));
+        assert_eq!(calls.get(), 1);
+    }
+
+    /// The pauses let other tasks of a single-threaded executor run.
+    #[tokio::test]
+    async fn test_run_does_not_block() -> Result<(), Error> {
+        let ready = Cell::new(false);
+        let options = RetryOptions::new()
+            .max_wait(Duration::from_secs(5))
+            .backoff(Duration::from_millis(10), Duration::from_millis(10));
+        let (result, ()) = tokio::join!(
+            options.run(|| async {
+                match ready.get() {
+                    true => Ok(()),
+                    false => Err(refused()),
+                }
+            }),
+            async { ready.set(true) },
+        );
+        result
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        assert!(super::is_connection_error(&refused()));
+        assert!(super::is_connection_error(&Error::IOError(
+            std::io::ErrorKind::TimedOut.into()
+        )));
+        assert!(!super::is_connection_error(&Error::IOError(
+            std::io::ErrorKind::NotFound.into()
+        )));
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_is_connection_error_sqlx() {
+        assert!(super::is_connection_error(&Error::DBError(
+            sqlx::Error::Io(std::io::ErrorKind::ConnectionRefused.into())
+        )));
+        assert!(!super::is_connection_error(&Error::DBError(
+            sqlx::Error::Io(std::io::ErrorKind::NotFound.into())
+        )));
+    }
+
+    #[cfg(feature = "diesel")]
+    #[test]
+    fn test_is_connection_error_diesel() {
+        let bad_connection = |message: &str| {
+            Error::DBConnectionError(diesel::ConnectionError::BadConnection(message.to_owned()))
+        };
+        assert!(super::is_connection_error(&bad_connection(
+            "connection to server at \"127.0.0.1\", port 5432 failed: Connection refused"
+        )));
+        assert!(super::is_connection_error(&bad_connection(
+            "connection to server at \"127.0.0.1\", port 5432 failed: FATAL:  the database system is starting up"
+        )));
+        assert!(super::is_connection_error(&bad_connection(
+            "Can't connect to MySQL server on '127.0.0.1:3306' (111)"
+        )));
+        assert!(!super::is_connection_error(&bad_connection(
+            "connection to server at \"127.0.0.1\", port 5432 failed: FATAL:  password authentication failed for user \"root\""
+        )));
+        assert!(!super::is_connection_error(&bad_connection(
+            "Access denied for user 'root'@'172.17.0.1' (using password: YES)"
+        )));
+    }
+
+    /// A refused connection is retried, with both frameworks and with `pg_isready`, which
+    /// takes a process per attempt.
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_wait_for_database_refused() {
+        let options = RetryOptions::new()
+            .max_wait(Duration::from_millis(500))
+            .backoff(Duration::from_millis(10), Duration::from_millis(10));
+        let result = super::wait_for_database(
+            &options,
+            crate::Backend::Postgres,
+            "postgresql://root@127.0.0.1:1/postgres",
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::ConnectionRetriesExhausted(attempts)) if attempts.len() > 1
+        ));
+    }
+}
@@ -0,0 +1,560 @@
+//! Splitting SQL into statements, for linting migrations, loading dumps and comparing
+//! them.
+//!
+//! The splitter knows enough of each dialect to not split inside string literals
+//! (`postgres` `E'...'` ones with backslash escapes), quoted identifiers, comments (nested
+//! ones on `postgres`, `#` ones on MySQL), dollar-quoted `postgres` bodies, trigger bodies
+//! and the `DELIMITER` blocks of `mysqldump`. It keeps the conditional comments of MySQL,
+//! which the server runs, and drops the client-side `\` meta-commands of `psql`.
+
+use crate::Backend;
+
+/// What a token is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    /// Keyword, unquoted identifier or number.
+    Word,
+    /// String literal or dollar-quoted body, with its quotes.
+    String,
+    /// Quoted identifier, with its quotes.
+    QuotedIdentifier,
+    /// `--` comment, or `#` one on MySQL, without the line break.
+    LineComment,
+    /// `/* ... */` comment.
+    BlockComment,
+    /// `/*! ... */` comment of MySQL, which the server runs.
+    ConditionalComment,
+    Whitespace,
+    /// Any other character, `;` in trigger bodies included.
+    Punctuation,
+}
+
+/// A token of a statement, as written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token<'a> {
+    pub(crate) kind: TokenKind,
+    pub(crate) text: &'a str,
+    /// Line where the token starts, from 1.
+    pub(crate) line: usize,
+}
+
+impl Token<'_> {
+    /// Whether the token is more than whitespace or a comment the server ignores.
+    fn is_code(&self) -> bool {
+        !matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+}
+
+/// A statement, with the comments and whitespace before it but without its delimiter.
+#[derive(Debug, Default)]
+pub(crate) struct Statement<'a> {
+    /// Line where the code of the statement starts, 0 for comments alone.
+    pub(crate) line: usize,
+    pub(crate) tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Statement<'a> {
+    pub(crate) fn has_code(&self) -> bool {
+        self.line > 0
+    }
+
+    /// The statement as it is run: trimmed, without its comments.
+    pub(crate) fn sql(&self) -> String {
+        let mut sql = String::new();
+        for token in &self.tokens {
+            match token.kind {
+                TokenKind::LineComment => {}
+                TokenKind::BlockComment => sql.push(' '),
+                _ => sql.push_str(token.text),
+            }
+        }
+        sql.trim().to_owned()
+    }
+
+    fn push(&mut self, token: Token<'a>) {
+        if !self.has_code() && token.is_code() {
+            self.line = token.line;
+        }
+        self.tokens.push(token);
+    }
+
+    fn words(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Word)
+            .map(|token| token.text)
+    }
+
+    /// Whether a `;` ends the statement, or is inside a trigger body (`BEGIN ... END;`)
+    /// or a `BEGIN ATOMIC` function body, which may nest blocks and `CASE ... END`.
+    fn ends_at_semicolon(&self, backend: Backend) -> bool {
+        let is = |word: &str, keyword: &str| word.eq_ignore_ascii_case(keyword);
+        let is_block = match backend {
+            Backend::Sqlite | Backend::MySql => {
+                self.words().next().is_some_and(|word| is(word, "CREATE"))
+                    && self.words().take(4).any(|word| is(word, "TRIGGER"))
+                    && self.words().any(|word| is(word, "BEGIN"))
+            }
+            Backend::Postgres => self
+                .words()
+                .zip(self.words().skip(1))
+                .any(|(word, next)| is(word, "BEGIN") && is(next, "ATOMIC")),
+        };
+        !is_block || self.depth() <= 0
+    }
+
+    /// How many `BEGIN` and `CASE` are still open. The `END IF`, `END LOOP`, `END WHILE`
+    /// and `END REPEAT` of MySQL close blocks which aren't counted, and `END CASE` closes
+    /// a `CASE` statement.
+    fn depth(&self) -> i64 {
+        let mut depth = 0;
+        let mut words = self
+            .words()
+            .map(|word| word.to_ascii_uppercase())
+            .peekable();
+        while let Some(word) = words.next() {
+            match word.as_str() {
+                "BEGIN" | "CASE" => depth += 1,
+                "END" => match words.peek().map(|word| word.as_str()) {
+                    Some("IF" | "LOOP" | "WHILE" | "REPEAT") => {
+                        let _ = words.next();
+                    }
+                    Some("CASE") => {
+                        let _ = words.next();
+                        depth -= 1;
+                    }
+                    _ => depth -= 1,
+                },
+                _ => {}
+            }
+        }
+        depth
+    }
+}
+
+/// Walks the SQL one character at a time, counting lines.
+struct Cursor<'a> {
+    sql: &'a str,
+    position: usize,
+    line: usize,
+    at_line_start: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(sql: &'a str) -> Self {
+        Cursor {
+            sql,
+            position: 0,
+            line: 1,
+            at_line_start: true,
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        self.at_line_start = c == '\n';
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.sql[self.position..].chars().next()
+    }
+
+    /// Whether the remaining input starts with `prefix`, ignoring ASCII case.
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.sql[self.position..]
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    }
+
+    /// Consume `prefix`, which the remaining input starts with.
+    fn skip(&mut self, prefix: &str) {
+        for _ in prefix.chars() {
+            let _ = self.next();
+        }
+    }
+
+    /// Consume the rest of the line, without the line break.
+    fn rest_of_line(&mut self) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != '\n') {
+            let _ = self.next();
+        }
+        &self.sql[start..self.position]
+    }
+
+    /// Consume up to `end` included.
+    fn skip_until(&mut self, end: &str) {
+        while !self.starts_with(end) {
+            if self.next().is_none() {
+                return;
+            }
+        }
+        self.skip(end);
+    }
+
+    /// Consume a comment after its opening `/*`, up to the matching `*/` with `nested`.
+    fn skip_comment(&mut self, nested: bool) {
+        let mut depth = 1;
+        while depth > 0 {
+            if nested && self.starts_with("/*") {
+                self.skip("/*");
+                depth += 1;
+            } else if self.starts_with("*/") {
+                self.skip("*/");
+                depth -= 1;
+            } else if self.next().is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Consume a quoted literal or identifier after its opening `quote`, which a doubled
+    /// `quote` escapes, and so does a backslash with `backslash_escapes`.
+    fn skip_quoted(&mut self, quote: char, backslash_escapes: bool) {
+        while let Some(c) = self.next() {
+            if c == '\\' && backslash_escapes {
+                let _ = self.next();
+            } else if c == quote {
+                if self.peek() != Some(quote) {
+                    return;
+                }
+                let _ = self.next();
+            }
+        }
+    }
+
+    /// Read the rest of a `$tag$` opening a dollar-quoted string, the `$` being consumed.
+    /// A `$` followed by a digit (`$1`) doesn't open one.
+    fn dollar_tag(&mut self) -> Option<&'a str> {
+        let sql = self.sql;
+        let start = self.position - 1;
+        let mut chars = sql[self.position..].char_indices();
+        loop {
+            match chars.next()? {
+                (end, '$') => {
+                    let tag = &sql[start..=self.position + end];
+                    self.skip(&tag[1..]);
+                    return Some(tag);
+                }
+                (end, c)
+                    if c.is_ascii_alphabetic() || c == '_' || (c.is_ascii_digit() && end > 0) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    /// Read the token at the cursor. Words stop before `delimiter`.
+    fn token(&mut self, backend: Backend, delimiter: &str) -> Option<TokenKind> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+        let c = self.next()?;
+        Some(match c {
+            '-' if self.peek() == Some('-') => {
+                let _ = self.rest_of_line();
+                TokenKind::LineComment
+            }
+            '#' if backend == Backend::MySql => {
+                let _ = self.rest_of_line();
+                TokenKind::LineComment
+            }
+            '/' if self.peek() == Some('*') => {
+                self.skip("*");
+                if backend == Backend::MySql && self.peek() == Some('!') {
+                    self.skip_until("*/");
+                    TokenKind::ConditionalComment
+                } else {
+                    self.skip_comment(backend == Backend::Postgres);
+                    TokenKind::BlockComment
+                }
+            }
+            'E' | 'e' if backend == Backend::Postgres && self.peek() == Some('\'') => {
+                self.skip("'");
+                self.skip_quoted('\'', true);
+                TokenKind::String
+            }
+            '\'' => {
+                self.skip_quoted('\'', backend == Backend::MySql);
+                TokenKind::String
+            }
+            '"' | '`' => {
+                self.skip_quoted(c, backend == Backend::MySql && c == '"');
+                TokenKind::QuotedIdentifier
+            }
+            '[' if backend == Backend::Sqlite => {
+                self.skip_until("]");
+                TokenKind::QuotedIdentifier
+            }
+            // Words take in their `$`s, so this one starts a token.
+            '$' if backend == Backend::Postgres => match self.dollar_tag() {
+                Some(tag) => {
+                    self.skip_until(tag);
+                    TokenKind::String
+                }
+                None => TokenKind::Punctuation,
+            },
+            c if is_word(c) => {
+                while !self.starts_with(delimiter) && self.peek().is_some_and(is_word) {
+                    let _ = self.next();
+                }
+                TokenKind::Word
+            }
+            c if c.is_whitespace() => {
+                while self.peek().is_some_and(char::is_whitespace) {
+                    let _ = self.next();
+                }
+                TokenKind::Whitespace
+            }
+            _ => TokenKind::Punctuation,
+        })
+    }
+}
+
+/// Split `sql` into tokens, without splitting it into statements.
+pub(crate) fn tokenize(sql: &str, backend: Backend) -> Vec<Token<'_>> {
+    let mut cursor = Cursor::new(sql);
+    let mut tokens = Vec::new();
+    loop {
+        let (position, line) = (cursor.position, cursor.line);
+        let Some(kind) = cursor.token(backend, ";") else {
+            return tokens;
+        };
+        tokens.push(Token {
+            kind,
+            text: &sql[position..cursor.position],
+            line,
+        });
+    }
+}
+
+/// Split `sql` into the statements of `backend`. Comments after the last statement make
+/// a statement without code.
+pub(crate) fn split(sql: &str, backend: Backend) -> Vec<Statement<'_>> {
+    let mut cursor = Cursor::new(sql);
+    let mut statements = Vec::new();
+    let mut pending = Statement::default();
+    let mut delimiter = ";";
+
+    loop {
+        if cursor.at_line_start && !pending.has_code() {
+            // Client-side commands, which the server doesn't know about.
+            if backend == Backend::Postgres && cursor.peek() == Some('\\') {
+                let _ = cursor.rest_of_line();
+                continue;
+            }
+            if backend == Backend::MySql && cursor.starts_with("DELIMITER ") {
+                let line = cursor.rest_of_line()["DELIMITER ".len()..].trim();
+                if !line.is_empty() {
+                    delimiter = line;
+                }
+                continue;
+            }
+        }
+
+        if cursor.starts_with(delimiter) && (delimiter != ";" || pending.ends_at_semicolon(backend))
+        {
+            cursor.skip(delimiter);
+            if !pending.tokens.is_empty() {
+                statements.push(std::mem::take(&mut pending));
+            }
+            continue;
+        }
+
+        let (position, line) = (cursor.position, cursor.line);
+        let Some(kind) = cursor.token(backend, delimiter) else {
+            break;
+        };
+        pending.push(Token {
+            kind,
+            text: &sql[position..cursor.position],
+            line,
+        });
+    }
+    if !pending.tokens.is_empty() {
+        statements.push(pending);
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Backend;
+
+    fn split(sql: &str, backend: Backend) -> Vec<(usize, String)> {
+        super::split(sql, backend)
+            .into_iter()
+            .filter(|statement| statement.has_code())
+            .map(|statement| (statement.line, statement.sql()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_sqlite() {
+        let sql = "--
+--  Name: users; Type: table
+--
+CREATE TABLE users (
+  id TEXT PRIMARY KEY NOT NULL, -- the id; really
+  bio TEXT DEFAULT 'a; ''b'''
+);
+
+--
+--  Name: users_touch; Type: trigger
+--
+CREATE TRIGGER users_touch AFTER UPDATE ON users BEGIN
+  UPDATE users SET bio = ';' WHERE id = NEW.id;
+END;
+/* done; */ INSERT INTO \"a;b\" VALUES(1);";
+        assert_eq!(
+            split(sql, Backend::Sqlite),
+            vec![
+                (4, String::from("CREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL, \n  bio TEXT DEFAULT 'a; ''b'''\n)")),
+                (12, String::from("CREATE TRIGGER users_touch AFTER UPDATE ON users BEGIN\n  UPDATE users SET bio = ';' WHERE id = NEW.id;\nEND")),
+                (15, String::from("INSERT INTO \"a;b\" VALUES(1)")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_trigger_case() {
+        let sql = "CREATE TRIGGER users_role AFTER INSERT ON users BEGIN
+  UPDATE users SET role = CASE WHEN NEW.admin THEN 'admin' ELSE 'user' END;
+  SELECT CASE NEW.id WHEN '' THEN RAISE(ABORT, 'empty id') END;
+END;
+SELECT 1;";
+        assert_eq!(
+            split(sql, Backend::Sqlite),
+            vec![
+                (1, String::from("CREATE TRIGGER users_role AFTER INSERT ON users BEGIN\n  UPDATE users SET role = CASE WHEN NEW.admin THEN 'admin' ELSE 'user' END;\n  SELECT CASE NEW.id WHEN '' THEN RAISE(ABORT, 'empty id') END;\nEND")),
+                (5, String::from("SELECT 1")),
+            ]
+        );
+
+        let sql = "CREATE TRIGGER users_role BEFORE INSERT ON users FOR EACH ROW BEGIN
+  IF NEW.role IS NULL THEN
+    SET NEW.role = CASE WHEN NEW.admin THEN 'admin' ELSE 'user' END;
+  END IF;
+  CASE NEW.role WHEN 'root' THEN SET NEW.admin = 1; ELSE BEGIN END; END CASE;
+END;
+SELECT 1;";
+        assert_eq!(
+            split(sql, Backend::MySql),
+            vec![
+                (1, String::from("CREATE TRIGGER users_role BEFORE INSERT ON users FOR EACH ROW BEGIN\n  IF NEW.role IS NULL THEN\n    SET NEW.role = CASE WHEN NEW.admin THEN 'admin' ELSE 'user' END;\n  END IF;\n  CASE NEW.role WHEN 'root' THEN SET NEW.admin = 1; ELSE BEGIN END; END CASE;\nEND")),
+                (7, String::from("SELECT 1")),
+            ]
+        );
+
+        let sql = "CREATE FUNCTION f(int) RETURNS text LANGUAGE sql
+BEGIN ATOMIC
+ SELECT CASE WHEN $1 > 0 THEN 'positive' END;
+END;
+SELECT 1;";
+        assert_eq!(
+            split(sql, Backend::Postgres),
+            vec![
+                (1, String::from("CREATE FUNCTION f(int) RETURNS text LANGUAGE sql\nBEGIN ATOMIC\n SELECT CASE WHEN $1 > 0 THEN 'positive' END;\nEND")),
+                (5, String::from("SELECT 1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_postgres() {
+        let sql = "\\restrict abc
+SET client_encoding = 'UTF8';
+CREATE FUNCTION f() RETURNS text AS $body$ SELECT 'a;b'; $body$ LANGUAGE sql;
+CREATE FUNCTION g(int) RETURNS int LANGUAGE sql
+    BEGIN ATOMIC
+ SELECT $1;
+END;
+SELECT E'it\\'s;', $$;$$;
+\\unrestrict abc
+";
+        assert_eq!(
+            split(sql, Backend::Postgres),
+            vec![
+                (2, String::from("SET client_encoding = 'UTF8'")),
+                (3, String::from("CREATE FUNCTION f() RETURNS text AS $body$ SELECT 'a;b'; $body$ LANGUAGE sql")),
+                (4, String::from("CREATE FUNCTION g(int) RETURNS int LANGUAGE sql\n    BEGIN ATOMIC\n SELECT $1;\nEND")),
+                (8, String::from("SELECT E'it\\'s;', $$;$$")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_mysql() {
+        let sql = "/*!40101 SET NAMES utf8mb4 */;
+CREATE TABLE `a;b` (
+  `id` varchar(32) NOT NULL DEFAULT 'it\\'s;'
+);
+DELIMITER ;;
+CREATE PROCEDURE p()
+BEGIN
+  SELECT 1;
+END ;;
+DELIMITER ;
+SELECT 2;
+";
+        assert_eq!(
+            split(sql, Backend::MySql),
+            vec![
+                (1, String::from("/*!40101 SET NAMES utf8mb4 */")),
+                (
+                    2,
+                    String::from(
+                        "CREATE TABLE `a;b` (\n  `id` varchar(32) NOT NULL DEFAULT 'it\\'s;'\n)"
+                    )
+                ),
+                (
+                    6,
+                    String::from("CREATE PROCEDURE p()\nBEGIN\n  SELECT 1;\nEND")
+                ),
+                (11, String::from("SELECT 2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_comments() {
+        let sql = "/* outer /* inner; */ still; a comment */ SELECT 1;
+SELECT [a;b] FROM t;
+";
+        assert_eq!(
+            split(sql, Backend::Postgres),
+            vec![
+                (1, String::from("SELECT 1")),
+                (2, String::from("SELECT [a")),
+                (2, String::from("b] FROM t"))
+            ]
+        );
+        assert_eq!(
+            split(sql, Backend::Sqlite),
+            vec![
+                (1, String::from("still")),
+                (1, String::from("a comment */ SELECT 1")),
+                (2, String::from("SELECT [a;b] FROM t"))
+            ]
+        );
+
+        let sql = "# DROP TABLE users;
+SELECT 1; # trailing; comment
+";
+        assert_eq!(
+            split(sql, Backend::MySql),
+            vec![(2, String::from("SELECT 1"))]
+        );
+        assert_eq!(
+            super::split(sql, Backend::MySql)
+                .last()
+                .map(|statement| statement.has_code()),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Implementation of the `sqlite` feature for the `cli` backend, through the `sqlite3`
+//! command line shell
+
+use crate::error::Error;
+
+pub(crate) const DEFAULT_CONNECTION_URL: &str = "sqlite::memory:";
+
+/// The connection the operations of [`super`] run on.
+pub(super) type Connection = Sqlite3;
+
+/// Open the database of `connection_url`.
+pub(super) async fn connect(connection_url: &str) -> Result<Connection, Error> {
+    Sqlite3::open(connection_url)
+}
+
+/// A database opened with the `sqlite3` shell, which runs in a process of its own for
+/// every query. An in-memory database would be gone between them, so a temporary file,
+/// removed when dropped, stands in for it.
+pub(super) struct Sqlite3 {
+    path: std::path::PathBuf,
+    _temporary: Option<crate::process::SecretFile>,
+}
+
+impl Sqlite3 {
+    /// Open the database of `connection_url`, in the `sqlx` (`sqlite://app.db?mode=rwc`,
+    /// `sqlite::memory:`) or `diesel` (`app.db`, `:memory:`) form.
+    fn open(connection_url: &str) -> Result<Self, Error> {
+        let path = connection_url
+            .strip_prefix("sqlite://")
+            .or_else(|| connection_url.strip_prefix("sqlite:"))
+            .unwrap_or(connection_url);
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        if path.is_empty() || path == ":memory:" {
+            let temporary = crate::process::SecretFile::new("sqlite3", "")?;
+            return Ok(Self {
+                path: temporary.path().to_path_buf(),
+                _temporary: Some(temporary),
+            });
+        }
+        Ok(Self {
+            path: std::path::PathBuf::from(path),
+            _temporary: None,
+        })
+    }
+
+    /// Run `sql`, returning what it printed without the last line break: one line per
+    /// row, for queries of a single column.
+    ///
+    /// The shell refuses a last statement without a semicolon, and doesn't mind an empty
+    /// one, so one is always added.
+    #[allow(unused_results)]
+    async fn query(&self, sql: &str) -> Result<String, Error> {
+        let mut cmd = crate::process::Command::new("sqlite3");
+        cmd.arg("-batch")
+            .arg("-bail")
+            .arg("-noheader")
+            .arg("-list")
+            .arg(&self.path);
+        let mut output = crate::process::output(&mut cmd, Some(&format!("{sql}\n;"))).await?;
+        if output.ends_with('\n') {
+            let _ = output.pop();
+        }
+        Ok(output)
+    }
+}
+
+impl crate::plain::Execute for Sqlite3 {
+    async fn execute_sql(&mut self, sql: &str) -> Result<(), Error> {
+        let _ = self.query(sql).await?;
+        Ok(())
+    }
+
+    async fn fetch_versions(&mut self, query: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .query(query)
+            .await?
+            .lines()
+            .map(str::to_owned)
+            .collect())
+    }
+}
+
+/// Dump the schema, without `exclude_tables` and their indexes and triggers.
+///
+/// The entries are rendered by the query itself, since they span several lines.
+pub(super) async fn fetch_structure(
+    conn: &mut Sqlite3,
+    exclude_tables: &[String],
+) -> Result<String, Error> {
+    conn.query(&format!(
+        "SELECT group_concat('--' || char(10) || '--  Name: ' || name || '; Type: ' || mytype || char(10) || '--' || char(10) || sql || ';' || char(10), char(10))
+FROM ({})
+WHERE tbl_name NOT IN ({})",
+        super::SQLITE_SCHEMA_QUERY,
+        quote_all(exclude_tables)
+    ))
+    .await
+}
+
+/// Dump the rows of `table`.
+pub(super) async fn fetch_data(conn: &mut Sqlite3, table: &str) -> Result<String, Error> {
+    let columns = crate::plain::Execute::fetch_versions(conn, &super::columns_query(table)).await?;
+    if columns.is_empty() {
+        return Err(Error::InvalidDataTable(table.to_owned()));
+    }
+    let statements = conn
+        .query(&format!(
+            "SELECT group_concat(statement, char(10)) FROM ({})",
+            super::data_query(table, &columns)
+        ))
+        .await?;
+    Ok(super::data_section(
+        table,
+        &Vec::from_iter((!statements.is_empty()).then_some(statements)),
+    ))
+}
+
+/// `names` as a list of SQL string literals.
+fn quote_all(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("'{}'", name.replace('\'', "''")))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{migrations::MigrationSource, PlainMigrations};
+
+    #[tokio::test]
+    async fn test_fetch_structure_sql_plain() -> Result<(), crate::error::Error> {
+        let plain = PlainMigrations::new("./fixtures/plain/sqlite");
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.clone()),
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+        assert!(structure.starts_with("--\n--  Name: _database_schema_migrations; Type: table\n--\nCREATE TABLE _database_schema_migrations (\n"));
+        assert!(
+            structure.contains(");\n\n--\n--  Name: posts; Type: table\n--\nCREATE TABLE posts (")
+        );
+        assert!(structure.ends_with("--\n--  Name: users; Type: table\n--\nCREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL,\n  email TEXT NOT NULL\n, name TEXT);\n"));
+
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.untracked()),
+            &crate::DumpOptions {
+                exclude_tables: &[String::from("users")],
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(!structure.contains("_database_schema_migrations"));
+        assert!(!structure.contains("CREATE TABLE users"));
+        assert!(structure.contains("CREATE TABLE posts ("));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_structure_sql_data() -> Result<(), crate::error::Error> {
+        let migrations_dir = std::env::temp_dir().join("cli-sqlite-data-migrations");
+        let _ = std::fs::remove_dir_all(&migrations_dir);
+        std::fs::create_dir_all(&migrations_dir)?;
+        std::fs::write(
+            migrations_dir.join("1_create_roles.sql"),
+            "CREATE TABLE roles (id INTEGER PRIMARY KEY, name TEXT NOT NULL, icon BLOB);
+INSERT INTO roles VALUES (2, 'editor', NULL), (1, 'admin''s', X'01');",
+        )?;
+        let migrations = MigrationSource::Plain(PlainMigrations::new(&migrations_dir).untracked());
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                data_tables: &[String::from("main.roles")],
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(structure.ends_with(
+            "--\n--  Data for Name: main.roles; Type: table\n--\nINSERT INTO main.roles VALUES(1,'admin''s',X'01');\nINSERT INTO main.roles VALUES(2,'editor',NULL);\n"
+        ));
+
+        assert!(matches!(
+            crate::sqlite::fetch_structure_sql(super::DEFAULT_CONNECTION_URL, &migrations, &crate::DumpOptions { data_tables: &[String::from("permissions")], ..Default::default() })
+            .await,
+            Err(crate::error::Error::InvalidDataTable(table)) if table == "permissions"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load() -> Result<(), crate::error::Error> {
+        let migrations = MigrationSource::Plain(PlainMigrations::new("./fixtures/plain/sqlite"));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+
+        let path = std::env::temp_dir().join("cli-sqlite-load.db");
+        let _ = std::fs::remove_file(&path);
+        let connection_url = format!("sqlite://{}?mode=rwc", path.display());
+        assert!(crate::sqlite::load(&connection_url, &structure, &migrations, None).await? > 0);
+        assert_eq!(crate::sqlite::structure(&connection_url).await?, structure);
+        // The migrations are recorded as applied, so running them doesn't change anything
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                &connection_url,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+        Ok(())
+    }
+}
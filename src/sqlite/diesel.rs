@@ -2,24 +2,19 @@
 
 pub(crate) const DEFAULT_CONNECTION_URL: &str = ":memory:";
 
-pub(crate) async fn fetch_structure_sql<P: AsRef<std::path::Path>>(
-    connection_url: &str,
-    migrations_path: P,
-) -> Result<String, crate::error::Error> {
-    use diesel::Connection;
-    use diesel_migrations::{FileBasedMigrations, HarnessWithOutput, MigrationHarness};
-    let mut conn = diesel::SqliteConnection::establish(connection_url)?;
-    let migrations = FileBasedMigrations::from_path(migrations_path)?;
-    let _ = HarnessWithOutput::write_to_stdout(&mut conn)
-        .run_pending_migrations(migrations)
-        .map(|_| ());
-
-    Ok(fetch_structure(&mut conn).await?)
+/// The connection the operations of [`super`] run on.
+pub(super) type Connection = diesel::SqliteConnection;
+
+/// Open the database of `connection_url`.
+pub(super) async fn connect(connection_url: &str) -> Result<Connection, crate::error::Error> {
+    Ok(diesel::Connection::establish(connection_url)?)
 }
 
-async fn fetch_structure(
-    conn: &mut diesel::SqliteConnection,
-) -> Result<String, diesel::result::Error> {
+/// Dump the schema, without `exclude_tables` and their indexes and triggers.
+pub(super) async fn fetch_structure(
+    conn: &mut Connection,
+    exclude_tables: &[String],
+) -> Result<String, crate::error::Error> {
     use diesel::{sql_query, QueryableByName, RunQueryDsl};
 
     #[allow(unused_qualifications)]
@@ -29,6 +24,7 @@ async fn fetch_structure(
                 name -> Text,
                 mytype -> Text,
                 sql -> Text,
+                tbl_name -> Text,
             }
         }
     }
@@ -41,11 +37,17 @@ async fn fetch_structure(
         name: String,
         mytype: String,
         sql: String,
+        tbl_name: String,
     }
 
     let results: Vec<SqliteSchema> = sql_query(super::SQLITE_SCHEMA_QUERY).load(conn)?;
     Ok(results
         .iter()
+        .filter(|r| {
+            !exclude_tables
+                .iter()
+                .any(|table| table == r.tbl_name.as_str())
+        })
         .map(|r| {
             format!(
                 "--\n--  Name: {}; Type: {}\n--\n{};\n",
@@ -56,16 +58,387 @@ async fn fetch_structure(
         .join("\n"))
 }
 
+/// Dump the rows of `table`.
+pub(super) async fn fetch_data(
+    conn: &mut Connection,
+    table: &str,
+) -> Result<String, crate::error::Error> {
+    use diesel::{sql_query, sql_types::Text, QueryableByName, RunQueryDsl};
+
+    #[derive(Debug, QueryableByName)]
+    struct Column {
+        #[diesel(sql_type = Text)]
+        name: String,
+    }
+
+    #[derive(Debug, QueryableByName)]
+    struct Statement {
+        #[diesel(sql_type = Text)]
+        statement: String,
+    }
+
+    let columns = sql_query(super::columns_query(table))
+        .load::<Column>(conn)?
+        .into_iter()
+        .map(|column| column.name)
+        .collect::<Vec<String>>();
+    if columns.is_empty() {
+        return Err(crate::error::Error::InvalidDataTable(table.to_owned()));
+    }
+    let statements = sql_query(super::data_query(table, &columns))
+        .load::<Statement>(conn)?
+        .into_iter()
+        .map(|row| row.statement)
+        .collect::<Vec<String>>();
+    Ok(super::data_section(table, &statements))
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(all(feature = "diesel", feature = "sqlite"))]
     #[tokio::test]
     async fn test_fetch_structure_sql() -> Result<(), crate::error::Error> {
-        let migrations_path = std::path::PathBuf::from("./fixtures/diesel/sqlite/migrations");
-        let structure =
-            super::fetch_structure_sql(super::DEFAULT_CONNECTION_URL, migrations_path).await?;
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
 
         assert!(structure.contains("--\n--  Name: __diesel_schema_migrations; Type: table\n--\nCREATE TABLE __diesel_schema_migrations (\n       version VARCHAR(50) PRIMARY KEY NOT NULL,\n       run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP\n);\n\n--\n--  Name: users; Type: table\n--\nCREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL,\n  email TEXT NOT NULL,\n  created_at TEXT NOT NULL DEFAULT(datetime('now', 'utc'))\n)"));
         Ok(())
     }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_embedded() -> Result<(), crate::error::Error> {
+        const MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+            diesel_migrations::embed_migrations!("./fixtures/diesel/sqlite/migrations");
+
+        let embedded = crate::migrations::MigrationSource::Embedded(&MIGRATIONS);
+        let directory = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/migrations",
+        ));
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &embedded,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &directory,
+                &crate::DumpOptions::default()
+            )
+            .await?
+        );
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_verify_down_migrations() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, DownMigrationProblem};
+
+        let migrations = MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/down-migrations",
+        ));
+        let report =
+            crate::sqlite::verify_down_migrations(super::DEFAULT_CONNECTION_URL, &migrations)
+                .await?;
+        assert!(!report.is_ok());
+        assert_eq!(report.verified(), ["20230901000000"]);
+        assert_eq!(report.unchecked(), ["20230903000000"]);
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.failures()[0].version(), "20230902000000");
+        assert_eq!(
+            report.failures()[0].problem(),
+            &DownMigrationProblem::SchemaMismatch {
+                missing: vec![],
+                unexpected: vec![
+                    String::from("--"),
+                    String::from("--  Name: users_email; Type: index"),
+                    String::from("--"),
+                    String::from("CREATE INDEX users_email ON users (email);"),
+                ],
+            }
+        );
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_history() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/down-migrations",
+        ));
+        let history_dir = std::env::temp_dir().join("diesel-sqlite-schema-history");
+        let _ = std::fs::remove_dir_all(&history_dir);
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                history_dir: Some(&history_dir),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let snapshot =
+            |version: &str| std::fs::read_to_string(history_dir.join(format!("{version}.sql")));
+        assert!(!snapshot("20230901000000")?.contains("CREATE TABLE posts"));
+        assert!(snapshot("20230902000000")?.contains("CREATE INDEX users_email"));
+        assert!(!snapshot("20230902000000")?.contains("CREATE TABLE comments"));
+        assert_eq!(snapshot("20230903000000")?, structure);
+        assert_eq!(std::fs::read_dir(&history_dir)?.count(), 3);
+        Ok(())
+    }
+
+    /// A failing migration fails the dump, with or without a target version.
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_failing_migration() -> Result<(), crate::error::Error> {
+        let migrations_dir = std::env::temp_dir().join("diesel-sqlite-failing-migrations");
+        let _ = std::fs::remove_dir_all(&migrations_dir);
+        let migration_dir = migrations_dir.join("2023-09-01-000000_broken");
+        std::fs::create_dir_all(&migration_dir)?;
+        std::fs::write(
+            migration_dir.join("up.sql"),
+            "CREATE TABL broken (id TEXT);",
+        )?;
+        std::fs::write(migration_dir.join("down.sql"), "")?;
+        let migrations = crate::migrations::MigrationSource::Directory(migrations_dir);
+        for target in [None, Some("20230901000000")] {
+            assert!(matches!(
+                crate::sqlite::fetch_structure_sql(
+                    super::DEFAULT_CONNECTION_URL,
+                    &migrations,
+                    &crate::DumpOptions {
+                        target,
+                        ..Default::default()
+                    }
+                )
+                .await,
+                Err(crate::error::Error::MigrationHarnessError(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_target() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/down-migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                target: Some("20230902000000"),
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(structure.contains("CREATE TABLE posts"));
+        assert!(!structure.contains("CREATE TABLE comments"));
+
+        let history_dir = std::env::temp_dir().join("diesel-sqlite-target-schema-history");
+        let _ = std::fs::remove_dir_all(&history_dir);
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &migrations,
+                &crate::DumpOptions {
+                    history_dir: Some(&history_dir),
+                    target: Some("20230902000000"),
+                    ..Default::default()
+                }
+            )
+            .await?,
+            structure
+        );
+        assert_eq!(std::fs::read_dir(&history_dir)?.count(), 2);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_plain() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, PlainMigrations};
+
+        let plain = PlainMigrations::new("./fixtures/plain/sqlite");
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.clone()),
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+        assert!(structure.contains("CREATE TABLE _database_schema_migrations ("));
+        assert!(structure.contains("CREATE TABLE posts ("));
+        assert!(structure.contains("CREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL,\n  email TEXT NOT NULL\n, name TEXT)"));
+
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.untracked()),
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+        assert!(!structure.contains("_database_schema_migrations"));
+        assert!(structure.contains("CREATE TABLE posts ("));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_squash() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, SquashOptions};
+
+        let dir = std::env::temp_dir().join("diesel-sqlite-squash");
+        let _ = std::fs::remove_dir_all(&dir);
+        for entry in std::fs::read_dir("./fixtures/diesel/sqlite/down-migrations")? {
+            let entry = entry?;
+            let migration_dir = dir.join("migrations").join(entry.file_name());
+            std::fs::create_dir_all(&migration_dir)?;
+            for file in ["up.sql", "down.sql"] {
+                let _ = std::fs::copy(entry.path().join(file), migration_dir.join(file))?;
+            }
+        }
+        let migrations = MigrationSource::Directory(dir.join("migrations"));
+        let connection_url = dir.join("app.db").display().to_string();
+        let structure = crate::sqlite::fetch_structure_sql(
+            &connection_url,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+
+        let options = SquashOptions::new("20230902000000");
+        let plan = crate::squash::plan(&migrations, &options)?;
+        let sql =
+            crate::sqlite::baseline(super::DEFAULT_CONNECTION_URL, &migrations, "20230902000000")
+                .await?;
+        assert!(!sql.contains("__diesel_schema_migrations"));
+        let baseline = crate::squash::write(plan, &options, &sql)?;
+        assert_eq!(
+            baseline.path(),
+            dir.join("migrations/2023-09-02-000000_baseline")
+        );
+        assert_eq!(
+            baseline.squashed(),
+            ["2023-09-01-000000_add_users", "2023-09-02-000000_add_posts"]
+        );
+        assert_eq!(std::fs::read_dir(dir.join("migrations"))?.count(), 2);
+
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+
+        // diesel only records versions, the database migrated before the squash is fine
+        assert!(
+            !crate::sqlite::adopt_baseline(&connection_url, &migrations, "20230902000000").await?
+        );
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                &connection_url,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_data() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, PlainMigrations};
+
+        let migrations_dir = std::env::temp_dir().join("diesel-sqlite-data-migrations");
+        let _ = std::fs::remove_dir_all(&migrations_dir);
+        std::fs::create_dir_all(&migrations_dir)?;
+        std::fs::write(
+            migrations_dir.join("1_create_roles.sql"),
+            "CREATE TABLE roles (id INTEGER PRIMARY KEY, name TEXT NOT NULL, icon BLOB);
+INSERT INTO roles VALUES (2, 'editor', NULL), (1, 'admin''s', X'01');",
+        )?;
+        let migrations = MigrationSource::Plain(PlainMigrations::new(&migrations_dir).untracked());
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                data_tables: &[String::from("main.roles")],
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(structure.ends_with(
+            "--\n--  Data for Name: main.roles; Type: table\n--\nINSERT INTO main.roles VALUES(1,'admin''s',X'01');\nINSERT INTO main.roles VALUES(2,'editor',NULL);\n"
+        ));
+
+        assert!(matches!(
+            crate::sqlite::fetch_structure_sql(super::DEFAULT_CONNECTION_URL, &migrations, &crate::DumpOptions { data_tables: &[String::from("permissions")], ..Default::default() })
+            .await,
+            Err(crate::error::Error::InvalidDataTable(table)) if table == "permissions"
+        ));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "diesel", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_load() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/diesel/sqlite/migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+
+        let path = std::env::temp_dir().join("diesel-sqlite-load.db");
+        let _ = std::fs::remove_file(&path);
+        let connection_url = format!("sqlite://{}?mode=rwc", path.display());
+        assert!(crate::sqlite::load(&connection_url, &structure, &migrations, None).await? > 0);
+        let mut conn =
+            <diesel::SqliteConnection as diesel::Connection>::establish(&connection_url)?;
+        assert_eq!(super::fetch_structure(&mut conn, &[]).await?, structure);
+        // The migrations are recorded as applied, so running them doesn't change anything
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                &connection_url,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+
+        // Failing statements don't stop the load, and are all reported
+        let sql = "CREATE TABLE roles (id INTEGER PRIMARY KEY);\n\nCREATE TABLE roles (id INTEGER);\n-- Typo\nSELEC 1;\nCREATE TABLE permissions (id INTEGER PRIMARY KEY);\n";
+        let _ = std::fs::remove_file(&path);
+        match crate::sqlite::load(&connection_url, sql, &migrations, None).await {
+            Err(crate::error::Error::LoadFailed(errors)) => assert_eq!(
+                errors
+                    .iter()
+                    .map(|error| (error.line(), error.statement()))
+                    .collect::<Vec<(usize, &str)>>(),
+                vec![(3, "CREATE TABLE roles (id INTEGER)"), (5, "SELEC 1")]
+            ),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        Ok(())
+    }
 }
@@ -5,33 +5,191 @@
 // the generated shell.c in this mirror
 // https://github.com/smparkes/sqlite/blob/8caf9219240123fbe6cff67b1e0da778c62d7621/src/shell.c#L2063
 // (they are somewhat out of sync but the query is the same)
-const SQLITE_SCHEMA_QUERY: &str = "SELECT name, type as mytype, sql
+const SQLITE_SCHEMA_QUERY: &str = "SELECT name, type as mytype, sql, tbl_name
 FROM sqlite_schema
 WHERE
   sql NOTNULL AND
   name NOT LIKE 'sqlite_%'
 ORDER BY tbl_name, type DESC, name";
 
-#[cfg(any(feature = "sqlx", feature = "diesel"))]
-pub(crate) async fn write_structure_sql<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn write_structure_sql<Q: AsRef<std::path::Path>>(
     connection_url: &str,
-    migrations_path: P,
+    migrations: &crate::migrations::MigrationSource,
     destination_path: Q,
+    options: &crate::DumpOptions<'_>,
 ) -> Result<(), crate::error::Error> {
-    let structure_sql = fetch_structure_sql(connection_url, migrations_path).await?;
+    let structure_sql = fetch_structure_sql(connection_url, migrations, options).await?;
     Ok(std::fs::write(destination_path, structure_sql)?)
 }
 
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+async fn fetch_structure_sql(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    options: &crate::DumpOptions<'_>,
+) -> Result<String, crate::error::Error> {
+    let mut conn = connect(connection_url).await?;
+    match options.history_dir {
+        Some(history_dir) => {
+            crate::history::migrate(
+                &mut conn,
+                migrations,
+                &SqliteSchema,
+                history_dir,
+                options.target,
+            )
+            .await?
+        }
+        None => migrations.run(&mut conn, options.target).await?,
+    }
+
+    let mut structure = fetch_structure(&mut conn, options.exclude_tables).await?;
+    for table in options.data_tables {
+        structure.push('\n');
+        structure.push_str(&fetch_data(&mut conn, table).await?);
+    }
+    Ok(structure)
+}
+
+/// Check that the down migrations restore the schema.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn verify_down_migrations(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+) -> Result<crate::DownMigrationReport, crate::error::Error> {
+    let mut conn = connect(connection_url).await?;
+    crate::verify::verify(&mut conn, migrations, &SqliteSchema).await
+}
+
+/// Migrate up to `version` and dump the schema, without the migrations table, as a
+/// baseline migration.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn baseline(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    version: &str,
+) -> Result<String, crate::error::Error> {
+    let mut conn = connect(connection_url).await?;
+    migrations.run(&mut conn, Some(version)).await?;
+    fetch_structure(
+        &mut conn,
+        &Vec::from_iter(migrations.table().map(str::to_owned)),
+    )
+    .await
+}
+
+/// Record the baseline `version` as applied on a database migrated before the squash.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn adopt_baseline(
+    connection_url: &str,
+    migrations: &crate::migrations::MigrationSource,
+    version: &str,
+) -> Result<bool, crate::error::Error> {
+    let mut conn = connect(connection_url).await?;
+    crate::squash::adopt(&mut conn, migrations, version, crate::Backend::Sqlite).await
+}
+
+/// Dump the schema of the database as it is, without running the migrations.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn structure(connection_url: &str) -> Result<String, crate::error::Error> {
+    fetch_structure(&mut connect(connection_url).await?, &[]).await
+}
+
+/// Run the statements of the dump `sql`, then record the `migrations` it was dumped
+/// from as applied.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+pub(crate) async fn load(
+    connection_url: &str,
+    sql: &str,
+    migrations: &crate::migrations::MigrationSource,
+    target: Option<&str>,
+) -> Result<usize, crate::error::Error> {
+    let mut conn = connect(connection_url).await?;
+    let statements = crate::load::run(&mut conn, sql, crate::Backend::Sqlite).await?;
+    let _ = migrations
+        .record(&mut conn, target, crate::Backend::Sqlite)
+        .await?;
+    Ok(statements)
+}
+
+/// Reads the schema on the migration connection itself, which for an in-memory database
+/// is the only one that sees it.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+struct SqliteSchema;
+
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+impl crate::migrations::Snapshot<Connection> for SqliteSchema {
+    async fn snapshot(&self, conn: &mut Connection) -> Result<String, crate::error::Error> {
+        fetch_structure(conn, &[]).await
+    }
+}
+
+/// Query the columns of `table`, which may be qualified with a schema (`main.roles`).
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+fn columns_query(table: &str) -> String {
+    match table.split_once('.') {
+        Some((schema, table)) => {
+            format!("SELECT name FROM pragma_table_info('{table}', '{schema}') ORDER BY cid")
+        }
+        None => format!("SELECT name FROM pragma_table_info('{table}') ORDER BY cid"),
+    }
+}
+
+/// Query the rows of `table` as `INSERT` statements, sorted by all of its `columns`.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+fn data_query(table: &str, columns: &[String]) -> String {
+    let columns = columns
+        .iter()
+        .map(|column| format!("\"{}\"", column.replace('"', "\"\"")))
+        .collect::<Vec<String>>();
+    format!(
+        "SELECT 'INSERT INTO {table} VALUES(' || {} || ');' AS statement FROM {} ORDER BY {}",
+        columns
+            .iter()
+            .map(|column| format!("quote({column})"))
+            .collect::<Vec<String>>()
+            .join(" || ',' || "),
+        table
+            .split('.')
+            .map(|part| format!("\"{part}\""))
+            .collect::<Vec<String>>()
+            .join("."),
+        columns.join(", ")
+    )
+}
+
+/// Render the rows of `table`, in the style of the schema entries.
+#[cfg(any(feature = "sqlx", feature = "diesel", feature = "cli"))]
+fn data_section(table: &str, statements: &[String]) -> String {
+    let mut section = format!("--\n--  Data for Name: {table}; Type: table\n--\n");
+    for statement in statements {
+        section.push_str(statement);
+        section.push('\n');
+    }
+    section
+}
+
+mod options;
+pub use options::SqliteConnectOptions;
+
 #[cfg(feature = "sqlx")]
 mod sqlx;
 #[cfg(feature = "sqlx")]
-use crate::sqlite::sqlx::fetch_structure_sql;
-#[cfg(feature = "sqlx")]
 pub(crate) use crate::sqlite::sqlx::DEFAULT_CONNECTION_URL;
+#[cfg(feature = "sqlx")]
+use crate::sqlite::sqlx::{connect, fetch_data, fetch_structure, Connection};
 
 #[cfg(feature = "diesel")]
 mod diesel;
 #[cfg(feature = "diesel")]
-use diesel::fetch_structure_sql;
-#[cfg(feature = "diesel")]
 pub(crate) use diesel::DEFAULT_CONNECTION_URL;
+#[cfg(feature = "diesel")]
+use diesel::{connect, fetch_data, fetch_structure, Connection};
+
+#[cfg(feature = "cli")]
+mod cli;
+#[cfg(feature = "cli")]
+pub(crate) use cli::DEFAULT_CONNECTION_URL;
+#[cfg(feature = "cli")]
+use cli::{connect, fetch_data, fetch_structure, Connection};
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+/// Typed options to open a SQLite database.
+///
+/// Pass them to [`DatabaseSchemaBuilder::connect_options`](crate::DatabaseSchemaBuilder::connect_options)
+/// instead of writing the `sqlx` or `diesel` flavour of the connection URL by hand.
+///
+/// ```rust,ignore
+/// let options = SqliteConnectOptions::new().filename("db/development.sqlite3");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SqliteConnectOptions {
+    pub(crate) filename: Option<PathBuf>,
+}
+
+impl SqliteConnectOptions {
+    /// Creates a new, default set of options: an in-memory database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path to the database file. It is created if it doesn't exist.
+    ///
+    /// By default set to `None`, which opens an in-memory database.
+    pub fn filename(mut self, filename: impl AsRef<Path>) -> Self {
+        self.filename = Some(filename.as_ref().to_path_buf());
+        self
+    }
+}
+
+impl crate::ConnectionOptions for SqliteConnectOptions {
+    fn connection_url(&self) -> String {
+        match self.filename {
+            None => String::from(super::DEFAULT_CONNECTION_URL),
+            #[cfg(any(feature = "sqlx", feature = "cli"))]
+            Some(ref filename) => format!("sqlite://{}?mode=rwc", filename.to_string_lossy()),
+            #[cfg(feature = "diesel")]
+            Some(ref filename) => filename.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteConnectOptions;
+    use crate::ConnectionOptions;
+
+    #[test]
+    fn test_connection_url() {
+        assert_eq!(
+            SqliteConnectOptions::new().connection_url(),
+            super::super::DEFAULT_CONNECTION_URL
+        );
+        #[cfg(any(feature = "sqlx", feature = "cli"))]
+        assert_eq!(
+            SqliteConnectOptions::new()
+                .filename("db/dev.sqlite3")
+                .connection_url(),
+            "sqlite://db/dev.sqlite3?mode=rwc"
+        );
+        #[cfg(feature = "diesel")]
+        assert_eq!(
+            SqliteConnectOptions::new()
+                .filename("db/dev.sqlite3")
+                .connection_url(),
+            "db/dev.sqlite3"
+        );
+    }
+}
@@ -2,28 +2,34 @@
 
 pub(crate) const DEFAULT_CONNECTION_URL: &str = "sqlite::memory:";
 
-pub(crate) async fn fetch_structure_sql<P: AsRef<std::path::Path>>(
-    connection_url: &str,
-    migrations_path: P,
-) -> Result<String, sqlx::Error> {
-    use sqlx::{migrate::Migrator, sqlite::SqliteConnectOptions, ConnectOptions};
+/// The connection the operations of [`super`] run on.
+pub(super) type Connection = sqlx::sqlite::SqliteConnection;
+
+/// Open the database of `connection_url`.
+pub(super) async fn connect(connection_url: &str) -> Result<Connection, crate::error::Error> {
+    use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions};
     use std::str::FromStr;
 
-    let mut conn = SqliteConnectOptions::from_str(connection_url)?
+    Ok(SqliteConnectOptions::from_str(connection_url)?
         .connect()
-        .await?;
-
-    let migrator = Migrator::new(migrations_path.as_ref()).await?;
-    migrator.run(&mut conn).await?;
-    fetch_structure(&mut conn).await
+        .await?)
 }
 
-async fn fetch_structure(conn: &mut sqlx::sqlite::SqliteConnection) -> Result<String, sqlx::Error> {
+/// Dump the schema, without `exclude_tables` and their indexes and triggers.
+pub(super) async fn fetch_structure(
+    conn: &mut Connection,
+    exclude_tables: &[String],
+) -> Result<String, crate::error::Error> {
     use sqlx::Row;
     let structure_dump = sqlx::query(super::SQLITE_SCHEMA_QUERY)
         .fetch_all(conn)
         .await?
         .iter()
+        .filter(|row| {
+            !exclude_tables
+                .iter()
+                .any(|table| table == row.get::<&str, _>(3))
+        })
         .map(|row| {
             let name = row.get::<String, _>(0);
             let r#type = row.get::<String, _>(1);
@@ -35,15 +41,386 @@ async fn fetch_structure(conn: &mut sqlx::sqlite::SqliteConnection) -> Result<St
     Ok(structure_dump)
 }
 
+/// Dump the rows of `table`.
+pub(super) async fn fetch_data(
+    conn: &mut Connection,
+    table: &str,
+) -> Result<String, crate::error::Error> {
+    let columns = sqlx::query_scalar::<_, String>(&super::columns_query(table))
+        .fetch_all(&mut *conn)
+        .await?;
+    if columns.is_empty() {
+        return Err(crate::error::Error::InvalidDataTable(table.to_owned()));
+    }
+    let statements = sqlx::query_scalar::<_, String>(&super::data_query(table, &columns))
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(super::data_section(table, &statements))
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(all(feature = "sqlx", feature = "sqlite"))]
     #[tokio::test]
     async fn test_fetch_structure_sql() -> Result<(), crate::error::Error> {
-        let migrations_path = std::path::PathBuf::from("./fixtures/sqlx/sqlite/migrations");
-        let structure =
-            super::fetch_structure_sql(super::DEFAULT_CONNECTION_URL, migrations_path).await?;
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
         assert!(structure.contains("--\n--  Name: _sqlx_migrations; Type: table\n--\nCREATE TABLE _sqlx_migrations (\n    version BIGINT PRIMARY KEY,\n    description TEXT NOT NULL,\n    installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,\n    success BOOLEAN NOT NULL,\n    checksum BLOB NOT NULL,\n    execution_time BIGINT NOT NULL\n);\n\n--\n--  Name: users; Type: table\n--\nCREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL,\n  email TEXT NOT NULL,\n  created_at TEXT NOT NULL DEFAULT(datetime('now', 'utc'))\n);\n"));
         Ok(())
     }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_embedded() -> Result<(), crate::error::Error> {
+        static MIGRATOR: sqlx::migrate::Migrator =
+            sqlx::migrate!("./fixtures/sqlx/sqlite/migrations");
+
+        let embedded = crate::migrations::MigrationSource::Embedded(&MIGRATOR);
+        let directory = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/migrations",
+        ));
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &embedded,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &directory,
+                &crate::DumpOptions::default()
+            )
+            .await?
+        );
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_verify_down_migrations() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, DownMigrationProblem};
+
+        let migrations = MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/down-migrations",
+        ));
+        let report =
+            crate::sqlite::verify_down_migrations(super::DEFAULT_CONNECTION_URL, &migrations)
+                .await?;
+        assert!(!report.is_ok());
+        assert_eq!(report.verified(), ["20230901000000"]);
+        assert_eq!(report.unchecked(), ["20230903000000"]);
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.failures()[0].version(), "20230902000000");
+        assert_eq!(
+            report.failures()[0].problem(),
+            &DownMigrationProblem::SchemaMismatch {
+                missing: vec![],
+                unexpected: vec![
+                    String::from("--"),
+                    String::from("--  Name: users_email; Type: index"),
+                    String::from("--"),
+                    String::from("CREATE INDEX users_email ON users (email);"),
+                ],
+            }
+        );
+
+        // Simple migrations can't be reverted
+        let migrations = MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/migrations",
+        ));
+        let report =
+            crate::sqlite::verify_down_migrations(super::DEFAULT_CONNECTION_URL, &migrations)
+                .await?;
+        assert_eq!(
+            report.failures()[0].problem(),
+            &DownMigrationProblem::Missing
+        );
+        assert!(report.unchecked().is_empty());
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_history() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/down-migrations",
+        ));
+        let history_dir = std::env::temp_dir().join("sqlx-sqlite-schema-history");
+        let _ = std::fs::remove_dir_all(&history_dir);
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                history_dir: Some(&history_dir),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let snapshot =
+            |version: &str| std::fs::read_to_string(history_dir.join(format!("{version}.sql")));
+        assert!(!snapshot("20230901000000")?.contains("CREATE TABLE posts"));
+        assert!(snapshot("20230902000000")?.contains("CREATE INDEX users_email"));
+        assert!(!snapshot("20230902000000")?.contains("CREATE TABLE comments"));
+        assert_eq!(snapshot("20230903000000")?, structure);
+        assert_eq!(std::fs::read_dir(&history_dir)?.count(), 3);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_target() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/down-migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                target: Some("20230902000000"),
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(structure.contains("CREATE TABLE posts"));
+        assert!(!structure.contains("CREATE TABLE comments"));
+
+        let history_dir = std::env::temp_dir().join("sqlx-sqlite-target-schema-history");
+        let _ = std::fs::remove_dir_all(&history_dir);
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &migrations,
+                &crate::DumpOptions {
+                    history_dir: Some(&history_dir),
+                    target: Some("20230902000000"),
+                    ..Default::default()
+                }
+            )
+            .await?,
+            structure
+        );
+        assert_eq!(std::fs::read_dir(&history_dir)?.count(), 2);
+        Ok(())
+    }
+
+    /// Migrating up to a version checks the applied migrations like a full run does.
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_target_changed() -> Result<(), crate::error::Error> {
+        let dir = std::env::temp_dir().join("sqlx-sqlite-target-changed");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("migrations"))?;
+        for entry in std::fs::read_dir("./fixtures/sqlx/sqlite/down-migrations")? {
+            let entry = entry?;
+            let _ = std::fs::copy(entry.path(), dir.join("migrations").join(entry.file_name()))?;
+        }
+        let migrations = crate::migrations::MigrationSource::Directory(dir.join("migrations"));
+        let connection_url = format!("sqlite://{}?mode=rwc", dir.join("app.db").display());
+        let _ = crate::sqlite::fetch_structure_sql(
+            &connection_url,
+            &migrations,
+            &crate::DumpOptions {
+                target: Some("20230901000000"),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        std::fs::write(
+            dir.join("migrations/20230901000000_add_users.up.sql"),
+            "CREATE TABLE users (id TEXT);",
+        )?;
+        assert!(matches!(
+            crate::sqlite::fetch_structure_sql(&connection_url, &migrations, &crate::DumpOptions { target: Some("20230902000000"), ..Default::default() })
+            .await,
+            Err(crate::error::Error::DBError(sqlx::Error::Migrate(error)))
+                if matches!(*error, sqlx::migrate::MigrateError::VersionMismatch(20230901000000))
+        ));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_plain() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, PlainMigrations};
+
+        let plain = PlainMigrations::new("./fixtures/plain/sqlite");
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.clone()),
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+        assert!(structure.contains("CREATE TABLE _database_schema_migrations ("));
+        assert!(structure.contains("CREATE TABLE posts ("));
+        assert!(structure.contains("CREATE TABLE users (\n  id TEXT PRIMARY KEY NOT NULL,\n  email TEXT NOT NULL\n, name TEXT)"));
+
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &MigrationSource::Plain(plain.untracked()),
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+        assert!(!structure.contains("_database_schema_migrations"));
+        assert!(structure.contains("CREATE TABLE posts ("));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_squash() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, SquashOptions};
+
+        let dir = std::env::temp_dir().join("sqlx-sqlite-squash");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("migrations"))?;
+        for entry in std::fs::read_dir("./fixtures/sqlx/sqlite/down-migrations")? {
+            let entry = entry?;
+            let _ = std::fs::copy(entry.path(), dir.join("migrations").join(entry.file_name()))?;
+        }
+        let migrations = MigrationSource::Directory(dir.join("migrations"));
+        let connection_url = format!("sqlite://{}?mode=rwc", dir.join("app.db").display());
+        let structure = crate::sqlite::fetch_structure_sql(
+            &connection_url,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+
+        let options = SquashOptions::new("20230902000000").archive_dir(dir.join("archive"));
+        let plan = crate::squash::plan(&migrations, &options)?;
+        let sql =
+            crate::sqlite::baseline(super::DEFAULT_CONNECTION_URL, &migrations, "20230902000000")
+                .await?;
+        assert!(!sql.contains("_sqlx_migrations"));
+        let baseline = crate::squash::write(plan, &options, &sql)?;
+        assert_eq!(baseline.version(), "20230902000000");
+        assert_eq!(
+            baseline.path(),
+            dir.join("migrations/20230902000000_baseline.sql")
+        );
+        assert_eq!(baseline.squashed().len(), 4);
+        assert_eq!(std::fs::read_dir(dir.join("archive"))?.count(), 4);
+
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                super::DEFAULT_CONNECTION_URL,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+
+        // The database migrated before the squash needs its history rewritten
+        assert!(
+            crate::sqlite::adopt_baseline(&connection_url, &migrations, "20230902000000").await?
+        );
+        assert!(
+            !crate::sqlite::adopt_baseline(&connection_url, &migrations, "20230902000000").await?
+        );
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                &connection_url,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_fetch_structure_sql_data() -> Result<(), crate::error::Error> {
+        use crate::{migrations::MigrationSource, PlainMigrations};
+
+        let migrations_dir = std::env::temp_dir().join("sqlx-sqlite-data-migrations");
+        let _ = std::fs::remove_dir_all(&migrations_dir);
+        std::fs::create_dir_all(&migrations_dir)?;
+        std::fs::write(
+            migrations_dir.join("1_create_roles.sql"),
+            "CREATE TABLE roles (id INTEGER PRIMARY KEY, name TEXT NOT NULL, icon BLOB);
+INSERT INTO roles VALUES (2, 'editor', NULL), (1, 'admin''s', X'01');",
+        )?;
+        let migrations = MigrationSource::Plain(PlainMigrations::new(&migrations_dir).untracked());
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions {
+                data_tables: &[String::from("main.roles")],
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(structure.ends_with(
+            "--\n--  Data for Name: main.roles; Type: table\n--\nINSERT INTO main.roles VALUES(1,'admin''s',X'01');\nINSERT INTO main.roles VALUES(2,'editor',NULL);\n"
+        ));
+
+        assert!(matches!(
+            crate::sqlite::fetch_structure_sql(super::DEFAULT_CONNECTION_URL, &migrations, &crate::DumpOptions { data_tables: &[String::from("permissions")], ..Default::default() })
+            .await,
+            Err(crate::error::Error::InvalidDataTable(table)) if table == "permissions"
+        ));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "sqlx", feature = "sqlite"))]
+    #[tokio::test]
+    async fn test_load() -> Result<(), crate::error::Error> {
+        let migrations = crate::migrations::MigrationSource::Directory(std::path::PathBuf::from(
+            "./fixtures/sqlx/sqlite/migrations",
+        ));
+        let structure = crate::sqlite::fetch_structure_sql(
+            super::DEFAULT_CONNECTION_URL,
+            &migrations,
+            &crate::DumpOptions::default(),
+        )
+        .await?;
+
+        let path = std::env::temp_dir().join("sqlx-sqlite-load.db");
+        let _ = std::fs::remove_file(&path);
+        let connection_url = format!("sqlite://{}?mode=rwc", path.display());
+        assert!(crate::sqlite::load(&connection_url, &structure, &migrations, None).await? > 0);
+        let mut conn = sqlx::ConnectOptions::connect(
+            &<sqlx::sqlite::SqliteConnectOptions as std::str::FromStr>::from_str(&connection_url)?,
+        )
+        .await?;
+        assert_eq!(super::fetch_structure(&mut conn, &[]).await?, structure);
+        // The migrations are recorded as applied, so running them doesn't change anything
+        assert_eq!(
+            crate::sqlite::fetch_structure_sql(
+                &connection_url,
+                &migrations,
+                &crate::DumpOptions::default()
+            )
+            .await?,
+            structure
+        );
+
+        // Failing statements don't stop the load, and are all reported
+        let sql = "CREATE TABLE roles (id INTEGER PRIMARY KEY);\n\nCREATE TABLE roles (id INTEGER);\n-- Typo\nSELEC 1;\nCREATE TABLE permissions (id INTEGER PRIMARY KEY);\n";
+        let _ = std::fs::remove_file(&path);
+        match crate::sqlite::load(&connection_url, sql, &migrations, None).await {
+            Err(crate::error::Error::LoadFailed(errors)) => assert_eq!(
+                errors
+                    .iter()
+                    .map(|error| (error.line(), error.statement()))
+                    .collect::<Vec<(usize, &str)>>(),
+                vec![(3, "CREATE TABLE roles (id INTEGER)"), (5, "SELEC 1")]
+            ),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,425 @@
+//! Squashing old migrations into a single baseline migration.
+//!
+//! The migrations up to a version are run against a scratch database, whose schema is
+//! dumped (without the migrations table) into a baseline migration replacing them. The
+//! baseline takes the version of the last migration it replaces, so that databases that
+//! applied it already know the baseline as applied: `diesel` and plain migrations only
+//! record versions, while `sqlx` also checks the checksums of the applied migrations and
+//! needs [`DatabaseSchema::adopt_baseline`](crate::DatabaseSchema::adopt_baseline).
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::Error,
+    migrations::{version_order, MigrationSource},
+};
+
+/// What to squash and what to do with the squashed migrations.
+///
+/// ```rust,ignore
+/// let baseline = DatabaseSchemaBuilder::new()
+///     .build()
+///     .squash(&SquashOptions::new("20240101000000").archive_dir("migrations-archive"))
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SquashOptions {
+    pub(crate) version: String,
+    pub(crate) name: String,
+    pub(crate) archive_dir: Option<PathBuf>,
+}
+
+impl SquashOptions {
+    /// Squash the migrations up to `version` included.
+    pub fn new<S: Into<String>>(version: S) -> Self {
+        Self {
+            version: version.into(),
+            name: String::from("baseline"),
+            archive_dir: None,
+        }
+    }
+
+    /// Name of the baseline migration, after its version. Defaults to `baseline`.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Move the squashed migrations to `archive_dir` instead of deleting them.
+    pub fn archive_dir<P: AsRef<Path>>(mut self, archive_dir: P) -> Self {
+        self.archive_dir = Some(archive_dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// Result of [`DatabaseSchema::squash`](crate::DatabaseSchema::squash).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Baseline {
+    pub(crate) version: String,
+    pub(crate) path: PathBuf,
+    pub(crate) squashed: Vec<String>,
+}
+
+impl Baseline {
+    /// Version of the baseline, the one of the last squashed migration.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The baseline migration file (`sqlx` and plain migrations) or directory (`diesel`).
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// File or directory names of the squashed migrations.
+    pub fn squashed(&self) -> &[String] {
+        &self.squashed
+    }
+}
+
+/// The migrations to squash, found before connecting to anything.
+#[derive(Debug)]
+pub(crate) struct Plan {
+    dir: PathBuf,
+    /// Squashed files and directories, sorted by version.
+    entries: Vec<(String, String)>,
+    /// Whether the baseline is a `diesel` migration directory.
+    is_diesel: bool,
+}
+
+impl Plan {
+    /// Version of the last squashed migration.
+    fn version(&self) -> &str {
+        self.entries
+            .last()
+            .map_or("", |(version, _)| version.as_str())
+    }
+}
+
+/// Find the migrations up to `options.version` in the directory of `migrations`.
+pub(crate) fn plan(migrations: &MigrationSource, options: &SquashOptions) -> Result<Plan, Error> {
+    if options.name.is_empty()
+        || !options
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(Error::MigrationSourceError(format!(
+            "invalid baseline name `{}`",
+            options.name
+        )));
+    }
+    let (dir, is_plain) = match migrations {
+        MigrationSource::Directory(dir) => (dir, false),
+        MigrationSource::Plain(plain) => (&plain.dir, true),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(Error::MigrationSourceError(String::from(
+                "embedded migrations can't be squashed, squash their directory instead",
+            )))
+        }
+    };
+
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir() && !name.ends_with(".sql") {
+            continue;
+        }
+        let version = match is_plain {
+            true => crate::plain::version_from_name(&name),
+            false => crate::migrations::version_from_name(&name),
+        };
+        if let Some(version) = version {
+            if version_order(&version) <= version_order(&options.version) {
+                entries.push((version, name));
+            }
+        }
+    }
+    entries.sort_by(|a, b| {
+        version_order(&a.0)
+            .cmp(&version_order(&b.0))
+            .then(a.1.cmp(&b.1))
+    });
+    if entries.is_empty() {
+        return Err(Error::MigrationSourceError(format!(
+            "no migration up to version {} in {}",
+            options.version,
+            dir.display()
+        )));
+    }
+    Ok(Plan {
+        dir: dir.clone(),
+        entries,
+        is_diesel: !is_plain && crate::FRAMEWORK == "diesel",
+    })
+}
+
+/// Replace the migrations of `plan` with a baseline migration made of `sql`.
+pub(crate) fn write(plan: Plan, options: &SquashOptions, sql: &str) -> Result<Baseline, Error> {
+    let version = plan.version().to_owned();
+    // Keep the version as it is written in the last migration (`2023-09-03-000000` with
+    // `diesel`, `V3` with refinery), since that's what its migration tool expects.
+    let prefix = plan
+        .entries
+        .last()
+        .and_then(|(_, name)| name.split('_').next())
+        .unwrap_or_default();
+    let path = match plan.is_diesel {
+        true => plan.dir.join(format!("{prefix}_{}", options.name)),
+        false => plan.dir.join(format!("{prefix}_{}.sql", options.name)),
+    };
+    if path.exists() {
+        return Err(Error::MigrationSourceError(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+
+    // Write the baseline under a temporary name first, so that the squashed migrations are
+    // only removed once it's safely on disk. It can't take its final name before they're
+    // gone: with `sqlx`, two migrations with the same version can't live side by side.
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = plan.dir.join(format!(".{file_name}.tmp"));
+    let up = format!(
+        "-- Baseline of the migrations up to {version}, dumped by database-schema.\n\n{}\n",
+        sql.trim()
+    );
+    if let Err(error) = write_baseline(&temporary, plan.is_diesel, &up) {
+        let _ = remove(&temporary);
+        return Err(error.into());
+    }
+
+    if let Some(ref archive_dir) = options.archive_dir {
+        std::fs::create_dir_all(archive_dir)?;
+    }
+    for (_, name) in &plan.entries {
+        let entry = plan.dir.join(name);
+        match options.archive_dir {
+            Some(ref archive_dir) => std::fs::rename(&entry, archive_dir.join(name))?,
+            None => remove(&entry)?,
+        }
+    }
+    std::fs::rename(&temporary, &path)?;
+    tracing::info!(
+        version,
+        path = %path.display(),
+        squashed = plan.entries.len(),
+        "Wrote baseline migration"
+    );
+    Ok(Baseline {
+        version,
+        path,
+        squashed: plan.entries.into_iter().map(|(_, name)| name).collect(),
+    })
+}
+
+/// Write the `up` SQL of a baseline at `path`, as a `diesel` migration directory or as a
+/// single file.
+fn write_baseline(path: &Path, is_diesel: bool, up: &str) -> std::io::Result<()> {
+    remove(path)?;
+    match is_diesel {
+        true => {
+            std::fs::create_dir(path)?;
+            std::fs::write(path.join("up.sql"), up)?;
+            std::fs::write(
+                path.join("down.sql"),
+                "-- A baseline can't be reverted, recreate the database instead.\n",
+            )
+        }
+        false => std::fs::write(path, up),
+    }
+}
+
+/// Remove the file or directory at `path`, if there is one.
+fn remove(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Whether a database that applied the `applied` versions is past the baseline `version`,
+/// failing if it is in between the squashed migrations.
+fn is_past_baseline(applied: &[String], version: &str) -> Result<bool, Error> {
+    if applied.iter().any(|applied| applied == version) {
+        return Ok(true);
+    }
+    if applied
+        .iter()
+        .any(|applied| version_order(applied) < version_order(version))
+    {
+        return Err(Error::PartiallyMigrated(version.to_owned()));
+    }
+    Ok(false)
+}
+
+/// Plain migrations only record versions, so there's nothing to rewrite.
+async fn adopt_plain<C: crate::plain::Execute>(
+    conn: &mut C,
+    migrations: &crate::plain::PlainMigrations,
+    version: &str,
+) -> Result<bool, Error> {
+    let Some(table) = migrations.checked_table()? else {
+        return Ok(false);
+    };
+    let applied = conn
+        .fetch_versions(&format!("SELECT version FROM {table}"))
+        .await?;
+    let _ = is_past_baseline(&applied, version)?;
+    Ok(false)
+}
+
+/// Make a database migrated with the squashed migrations see the baseline as applied:
+/// forget the squashed migrations, and record the baseline in place of the last one.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn adopt<C>(
+    conn: &mut C,
+    migrations: &MigrationSource,
+    version: &str,
+    backend: crate::Backend,
+) -> Result<bool, Error>
+where
+    C: sqlx::migrate::Migrate + crate::plain::Execute,
+{
+    if let MigrationSource::Plain(plain) = migrations {
+        return adopt_plain(conn, plain, version).await;
+    }
+    let baseline = migrations
+        .sqlx_migrations()
+        .await?
+        .into_iter()
+        .find(|migration| {
+            !migration.migration_type.is_down_migration()
+                && migration.version.to_string() == version
+        })
+        .ok_or_else(|| {
+            Error::MigrationSourceError(format!("no migration with version {version}"))
+        })?;
+    conn.ensure_migrations_table()
+        .await
+        .map_err(sqlx::Error::from)?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(sqlx::Error::from)?;
+    let versions = applied
+        .iter()
+        .map(|migration| migration.version.to_string())
+        .collect::<Vec<String>>();
+    if !is_past_baseline(&versions, version)? {
+        return Ok(false);
+    }
+    let is_adopted = applied.iter().all(|migration| {
+        migration.version > baseline.version
+            || (migration.version == baseline.version && migration.checksum == baseline.checksum)
+    });
+    if is_adopted {
+        return Ok(false);
+    }
+
+    let checksum = crate::migrations::checksum_literal(&baseline.checksum, backend);
+    tracing::info!(version, "Recording the baseline as applied");
+    conn.execute_sql(&format!(
+        "DELETE FROM _sqlx_migrations WHERE version < {0};
+UPDATE _sqlx_migrations SET description = '{1}', checksum = {checksum} WHERE version = {0};",
+        baseline.version,
+        baseline.description.replace('\'', "''"),
+    ))
+    .await?;
+    Ok(true)
+}
+
+/// Check that a database migrated with the squashed migrations sees the baseline as
+/// applied, which it does as long as it applied the last one: `diesel` only records
+/// versions, and doesn't mind applied migrations that are gone.
+#[cfg(feature = "diesel")]
+pub(crate) async fn adopt<DB, C>(
+    conn: &mut C,
+    migrations: &MigrationSource,
+    version: &str,
+    backend: crate::Backend,
+) -> Result<bool, Error>
+where
+    DB: diesel::backend::Backend,
+    C: diesel_migrations::MigrationHarness<DB> + crate::plain::Execute,
+{
+    let _ = backend;
+    if let MigrationSource::Plain(plain) = migrations {
+        return adopt_plain(conn, plain, version).await;
+    }
+    let applied = conn
+        .applied_migrations()
+        .map_err(|error| Error::MigrationHarnessError(error.to_string()))?
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>();
+    let _ = is_past_baseline(&applied, version)?;
+    Ok(false)
+}
+
+/// Make a database migrated with the squashed migrations see the baseline as applied,
+/// which plain migrations already do.
+#[cfg(feature = "cli")]
+pub(crate) async fn adopt<C: crate::plain::Execute>(
+    conn: &mut C,
+    migrations: &MigrationSource,
+    version: &str,
+    backend: crate::Backend,
+) -> Result<bool, Error> {
+    let _ = backend;
+    adopt_plain(conn, migrations.plain()?, version).await
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_is_past_baseline() -> Result<(), crate::error::Error> {
+        let applied = |versions: &[&str]| -> Vec<String> {
+            versions.iter().map(|version| version.to_string()).collect()
+        };
+        assert!(!super::is_past_baseline(&[], "3")?);
+        assert!(super::is_past_baseline(
+            &applied(&["1", "2", "3", "4"]),
+            "3"
+        )?);
+        assert!(super::is_past_baseline(&applied(&["3", "10"]), "3")?);
+        assert!(super::is_past_baseline(&applied(&["1", "2"]), "3").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> Result<(), crate::error::Error> {
+        let dir = std::env::temp_dir().join("database-schema-squash-write");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        for name in ["1_users.sql", "2_posts.sql", "3_comments.sql"] {
+            std::fs::write(dir.join(name), "SELECT 1;")?;
+        }
+        let plan = super::Plan {
+            dir: dir.clone(),
+            entries: vec![
+                (String::from("1"), String::from("1_users.sql")),
+                (String::from("2"), String::from("2_posts.sql")),
+            ],
+            is_diesel: false,
+        };
+        let baseline = super::write(
+            plan,
+            &super::SquashOptions::new("2"),
+            "CREATE TABLE t (id int);",
+        )?;
+        assert_eq!(baseline.path(), dir.join("2_baseline.sql"));
+
+        let mut names = std::fs::read_dir(&dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<String>, std::io::Error>>()?;
+        names.sort();
+        assert_eq!(names, vec!["2_baseline.sql", "3_comments.sql"]);
+        assert!(std::fs::read_to_string(baseline.path())?.ends_with("CREATE TABLE t (id int);\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+//! Checking that down migrations restore the schema their up migration started from.
+//!
+//! For every pending migration, we dump the schema, apply the migration, revert it, dump
+//! the schema again and compare both dumps, then apply the migration for good and move on
+//! to the next one. Like [`dump`](crate::DatabaseSchema::dump), this is meant to run
+//! against a scratch database: migrations that are already applied are left alone.
+
+use crate::error::Error;
+
+/// Result of [`DatabaseSchema::verify_down_migrations`](crate::DatabaseSchema::verify_down_migrations).
+///
+/// Verification stops at the first migration that fails to apply or that isn't reverted
+/// faithfully, since the database can't be trusted to be in the expected state after
+/// that. The migrations after it are reported as [`unchecked`](Self::unchecked).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DownMigrationReport {
+    pub(crate) verified: Vec<String>,
+    pub(crate) failures: Vec<DownMigrationFailure>,
+    pub(crate) unchecked: Vec<String>,
+}
+
+impl DownMigrationReport {
+    /// Whether every migration was checked and reverted faithfully.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && self.unchecked.is_empty()
+    }
+
+    /// Versions of the migrations whose down migration restored the previous schema.
+    pub fn verified(&self) -> &[String] {
+        &self.verified
+    }
+
+    /// Migrations whose down migration is missing, fails, or doesn't restore the previous
+    /// schema.
+    pub fn failures(&self) -> &[DownMigrationFailure] {
+        &self.failures
+    }
+
+    /// Versions of the migrations left unchecked after a failure.
+    pub fn unchecked(&self) -> &[String] {
+        &self.unchecked
+    }
+
+    fn failed(&mut self, version: String, name: String, problem: DownMigrationProblem) {
+        tracing::warn!(version, name, ?problem, "Down migration can't be trusted");
+        self.failures.push(DownMigrationFailure {
+            version,
+            name,
+            problem,
+        });
+    }
+
+    /// Stop after a failure that leaves the database in an unknown state, with the
+    /// `remaining` migrations unchecked.
+    fn abort(
+        mut self,
+        version: String,
+        name: String,
+        problem: DownMigrationProblem,
+        remaining: &[(String, String)],
+    ) -> Self {
+        self.failed(version, name, problem);
+        self.unchecked = remaining
+            .iter()
+            .map(|(version, _)| version.clone())
+            .collect();
+        self
+    }
+}
+
+impl std::fmt::Display for DownMigrationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} down migration(s) verified, {} failure(s), {} unchecked",
+            self.verified.len(),
+            self.failures.len(),
+            self.unchecked.len()
+        )?;
+        for failure in &self.failures {
+            writeln!(f, "{failure}")?;
+        }
+        if !self.unchecked.is_empty() {
+            writeln!(f, "unchecked: {}", self.unchecked.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A migration whose down migration can't be trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownMigrationFailure {
+    pub(crate) version: String,
+    pub(crate) name: String,
+    pub(crate) problem: DownMigrationProblem,
+}
+
+impl DownMigrationFailure {
+    /// Version of the migration.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Name (`diesel`) or description (`sqlx`) of the migration.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What went wrong.
+    pub fn problem(&self) -> &DownMigrationProblem {
+        &self.problem
+    }
+}
+
+impl std::fmt::Display for DownMigrationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}: ", self.version, self.name)?;
+        match self.problem {
+            DownMigrationProblem::Missing => write!(f, "no down migration"),
+            DownMigrationProblem::UpFailed(ref error) => write!(f, "up migration failed: {error}"),
+            DownMigrationProblem::DownFailed(ref error) => {
+                write!(f, "down migration failed: {error}")
+            }
+            DownMigrationProblem::SchemaMismatch {
+                ref missing,
+                ref unexpected,
+            } => {
+                write!(f, "the schema differs after the down migration")?;
+                for line in missing {
+                    write!(f, "\n  - {line}")?;
+                }
+                for line in unexpected {
+                    write!(f, "\n  + {line}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Why a down migration can't be trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownMigrationProblem {
+    /// The migration has no down migration.
+    Missing,
+
+    /// The up migration failed, before or after being reverted.
+    UpFailed(String),
+
+    /// The down migration failed.
+    DownFailed(String),
+
+    /// The schema after the down migration isn't the one before the up migration.
+    SchemaMismatch {
+        /// Lines of the dump before the up migration that are gone after the down
+        /// migration.
+        missing: Vec<String>,
+        /// Lines of the dump after the down migration that weren't there before the up
+        /// migration.
+        unexpected: Vec<String>,
+    },
+}
+
+/// Compare two dumps line by line, ignoring blank lines.
+#[cfg_attr(feature = "cli", allow(dead_code))]
+fn compare(before: &str, after: &str) -> Option<DownMigrationProblem> {
+    let mut unexpected = after
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<&str>>();
+    let mut missing = Vec::new();
+    for line in before.lines().filter(|line| !line.trim().is_empty()) {
+        match unexpected.iter().position(|other| *other == line) {
+            Some(position) => {
+                let _ = unexpected.remove(position);
+            }
+            None => missing.push(line.to_owned()),
+        }
+    }
+    (!missing.is_empty() || !unexpected.is_empty()).then(|| DownMigrationProblem::SchemaMismatch {
+        missing,
+        unexpected: unexpected.into_iter().map(str::to_owned).collect(),
+    })
+}
+
+/// Plain migrations have no down migrations: apply them, reporting every one as missing
+/// its down migration.
+async fn verify_plain<C: crate::plain::Execute>(
+    conn: &mut C,
+    migrations: &crate::plain::PlainMigrations,
+) -> Result<DownMigrationReport, Error> {
+    let pending = crate::plain::pending(conn, migrations).await?;
+    let names = pending
+        .iter()
+        .map(|migration| (migration.version.clone(), migration.name.clone()))
+        .collect::<Vec<(String, String)>>();
+
+    let mut report = DownMigrationReport::default();
+    for (index, migration) in pending.iter().enumerate() {
+        let (version, name) = names[index].clone();
+        if let Err(error) = crate::plain::apply(conn, migrations, migration).await {
+            let problem = DownMigrationProblem::UpFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, &names[index + 1..]));
+        }
+        report.failed(version, name, DownMigrationProblem::Missing);
+    }
+    Ok(report)
+}
+
+/// Apply, revert and re-apply every pending migration on `conn`, comparing the schema
+/// before and after each revert.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn verify<C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+) -> Result<DownMigrationReport, Error>
+where
+    C: sqlx::migrate::Migrate + crate::plain::Execute,
+    S: crate::migrations::Snapshot<C>,
+{
+    if let crate::migrations::MigrationSource::Plain(plain) = migrations {
+        return verify_plain(conn, plain).await;
+    }
+    let migrations = migrations.sqlx_migrations().await?;
+    conn.ensure_migrations_table()
+        .await
+        .map_err(sqlx::Error::from)?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .map_err(sqlx::Error::from)?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<Vec<i64>>();
+    let pending = migrations
+        .iter()
+        .filter(|migration| {
+            !migration.migration_type.is_down_migration() && !applied.contains(&migration.version)
+        })
+        .collect::<Vec<&sqlx::migrate::Migration>>();
+    let names = pending
+        .iter()
+        .map(|up| (up.version.to_string(), up.description.to_string()))
+        .collect::<Vec<(String, String)>>();
+    tracing::debug!(count = pending.len(), "Verifying down migrations");
+
+    let mut report = DownMigrationReport::default();
+    for (index, up) in pending.into_iter().enumerate() {
+        let (version, name) = names[index].clone();
+        let remaining = &names[index + 1..];
+        let down = migrations.iter().find(|migration| {
+            migration.version == up.version && migration.migration_type.is_down_migration()
+        });
+
+        let before = snapshot.snapshot(conn).await?;
+        if let Err(error) = conn.apply(up).await {
+            let problem = DownMigrationProblem::UpFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        let Some(down) = down else {
+            report.failed(version, name, DownMigrationProblem::Missing);
+            continue;
+        };
+        if let Err(error) = conn.revert(down).await {
+            let problem = DownMigrationProblem::DownFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        let reverted = snapshot.snapshot(conn).await?;
+        if let Some(problem) = compare(&before, &reverted) {
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        if let Err(error) = conn.apply(up).await {
+            let problem = DownMigrationProblem::UpFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        tracing::debug!(version, "Down migration restores the previous schema");
+        report.verified.push(version);
+    }
+    Ok(report)
+}
+
+/// Apply, revert and re-apply every pending migration on `conn`, comparing the schema
+/// before and after each revert.
+#[cfg(feature = "diesel")]
+pub(crate) async fn verify<DB, C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+) -> Result<DownMigrationReport, Error>
+where
+    DB: diesel::backend::Backend,
+    C: diesel_migrations::MigrationHarness<DB> + crate::plain::Execute,
+    S: crate::migrations::Snapshot<C>,
+{
+    use diesel::migration::MigrationSource;
+
+    if let crate::migrations::MigrationSource::Plain(plain) = migrations {
+        return verify_plain(conn, plain).await;
+    }
+
+    let harness_error = |error: Box<dyn std::error::Error + Send + Sync>| {
+        Error::MigrationHarnessError(error.to_string())
+    };
+    let mut migrations = migrations
+        .diesel_migrations()?
+        .migrations()
+        .map_err(harness_error)?;
+    migrations.sort_by(|a, b| a.name().version().cmp(&b.name().version()));
+    let applied = conn.applied_migrations().map_err(harness_error)?;
+    let pending = migrations
+        .into_iter()
+        .filter(|migration| !applied.contains(&migration.name().version()))
+        .collect::<Vec<Box<dyn diesel::migration::Migration<DB>>>>();
+    let names = pending
+        .iter()
+        .map(|migration| {
+            (
+                migration.name().version().to_string(),
+                migration.name().to_string(),
+            )
+        })
+        .collect::<Vec<(String, String)>>();
+    tracing::debug!(count = pending.len(), "Verifying down migrations");
+
+    let mut report = DownMigrationReport::default();
+    for (index, migration) in pending.into_iter().enumerate() {
+        let (version, name) = names[index].clone();
+        let remaining = &names[index + 1..];
+
+        let before = snapshot.snapshot(conn).await?;
+        if let Err(error) = conn.run_migration(&*migration) {
+            let problem = DownMigrationProblem::UpFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        if let Err(error) = conn.revert_migration(&*migration) {
+            // Nothing was reverted, so the next migrations can still be checked.
+            if matches!(
+                error.downcast_ref::<diesel_migrations::MigrationError>(),
+                Some(diesel_migrations::MigrationError::NoMigrationRevertFile)
+            ) {
+                report.failed(version, name, DownMigrationProblem::Missing);
+                continue;
+            }
+            let problem = DownMigrationProblem::DownFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        let reverted = snapshot.snapshot(conn).await?;
+        if let Some(problem) = compare(&before, &reverted) {
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        if let Err(error) = conn.run_migration(&*migration) {
+            let problem = DownMigrationProblem::UpFailed(error.to_string());
+            return Ok(report.abort(version, name, problem, remaining));
+        }
+        tracing::debug!(version, "Down migration restores the previous schema");
+        report.verified.push(version);
+    }
+    Ok(report)
+}
+
+/// Apply every pending migration on `conn`: without `sqlx` or `diesel`, they are plain
+/// migrations, which have no down migrations.
+#[cfg(feature = "cli")]
+pub(crate) async fn verify<C, S>(
+    conn: &mut C,
+    migrations: &crate::migrations::MigrationSource,
+    snapshot: &S,
+) -> Result<DownMigrationReport, Error>
+where
+    C: crate::plain::Execute,
+    S: crate::migrations::Snapshot<C>,
+{
+    let _ = snapshot;
+    verify_plain(conn, migrations.plain()?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DownMigrationProblem;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(
+            super::compare(
+                "CREATE TABLE a;\n\nCREATE TABLE b;\n",
+                "CREATE TABLE a;\nCREATE TABLE b;"
+            ),
+            None
+        );
+        assert_eq!(
+            super::compare(
+                "CREATE TABLE a;\nCREATE INDEX a_id;\n",
+                "CREATE TABLE a;\nCREATE TABLE c;\n"
+            ),
+            Some(DownMigrationProblem::SchemaMismatch {
+                missing: vec![String::from("CREATE INDEX a_id;")],
+                unexpected: vec![String::from("CREATE TABLE c;")],
+            })
+        );
+    }
+}